@@ -0,0 +1,253 @@
+#![allow(dead_code)]
+use core::fmt::Write;
+
+use crate::console::{self, Attribute, Color, Console, Rect};
+
+const ESC: u8 = 0x1b;
+// DOS end of file, .ANS files put their SAUCE record after it
+const SUB: u8 = 0x1a;
+const MAX_PARAMS: usize = 16;
+
+// ANSI color numbers are in a different order than the VGA ones
+const ANSI_TO_VGA: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+}
+
+// ANSI/VT100 escape sequence interpreter, driving a text Console
+pub struct Ansi {
+    state: State,
+    params: [u16; MAX_PARAMS],
+    param_count: usize,
+    // CSI ? sequences
+    private: bool,
+    // SGR state, colors are in ANSI order
+    fg: u8,
+    bg: u8,
+    bold: bool,
+    blink: bool,
+    reverse: bool,
+    saved_cursor: (usize, usize),
+}
+
+impl Default for Ansi {
+    fn default() -> Self { Self::new() }
+}
+
+impl Ansi {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            param_count: 0,
+            private: false,
+            fg: 7,
+            bg: 0,
+            bold: false,
+            blink: false,
+            reverse: false,
+            saved_cursor: (0, 0),
+        }
+    }
+
+    pub fn feed(&mut self, console: &mut Console, byte: u8) {
+        match self.state {
+            State::Ground => match byte {
+                ESC => self.state = State::Escape,
+                _ => console.write_byte(byte),
+            },
+            State::Escape => {
+                self.state = State::Ground;
+                match byte {
+                    b'[' => {
+                        self.params = [0; MAX_PARAMS];
+                        self.param_count = 0;
+                        self.private = false;
+                        self.state = State::Csi;
+                    }
+                    // DEC save/restore cursor
+                    b'7' => self.saved_cursor = console.cursor(),
+                    b'8' => console.set_cursor(self.saved_cursor.0, self.saved_cursor.1),
+                    // full reset
+                    b'c' => {
+                        *self = Self::new();
+                        self.apply_attr(console);
+                        console.clear();
+                    }
+                    _ => {}
+                }
+            }
+            State::Csi => match byte {
+                b'0'..=b'9' => {
+                    if self.param_count == 0 { self.param_count = 1; }
+                    let p = &mut self.params[self.param_count - 1];
+                    *p = p.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                }
+                b';' => {
+                    if self.param_count == 0 { self.param_count = 1; }
+                    if self.param_count < MAX_PARAMS { self.param_count += 1; }
+                }
+                b'?' => self.private = true,
+                // final bytes
+                0x40..=0x7e => {
+                    self.state = State::Ground;
+                    self.csi(console, byte);
+                }
+                // anything else we don't understand, drop the sequence
+                _ => self.state = State::Ground,
+            },
+        }
+    }
+
+    pub fn feed_bytes(&mut self, console: &mut Console, bytes: &[u8]) {
+        for &b in bytes {
+            self.feed(console, b);
+        }
+    }
+
+    // parameter `i`, with missing or zero parameters read as `default`
+    fn param(&self, i: usize, default: u16) -> usize {
+        match self.params[i] {
+            0 if i >= self.param_count || default != 0 => default as usize,
+            p => p as usize,
+        }
+    }
+
+    fn csi(&mut self, console: &mut Console, command: u8) {
+        let (col, row) = console.cursor();
+        let window = console.window();
+        match command {
+            // cursor up, down, forward, back
+            b'A' => console.set_cursor(col, row.saturating_sub(self.param(0, 1))),
+            b'B' => console.set_cursor(col, row + self.param(0, 1)),
+            b'C' => console.set_cursor(col + self.param(0, 1), row),
+            b'D' => console.set_cursor(col.saturating_sub(self.param(0, 1)), row),
+            // next line, previous line
+            b'E' => console.set_cursor(0, row + self.param(0, 1)),
+            b'F' => console.set_cursor(0, row.saturating_sub(self.param(0, 1))),
+            // cursor horizontal absolute
+            b'G' => console.set_cursor(self.param(0, 1) - 1, row),
+            // cursor position, 1 based
+            b'H' | b'f' => console.set_cursor(self.param(1, 1) - 1, self.param(0, 1) - 1),
+            // erase in display
+            b'J' => {
+                let (x, y) = (window.x + col, window.y + row);
+                let right = window.x + window.width;
+                let bottom = window.y + window.height;
+                let attr = console.attr;
+                match self.param(0, 0) {
+                    0 => {
+                        console.fill(Rect { x, y, width: right - x, height: 1 }, b' ', attr);
+                        console.fill(Rect { x: window.x, y: y + 1, width: window.width, height: bottom - y - 1 }, b' ', attr);
+                    }
+                    1 => {
+                        console.fill(Rect { x: window.x, y: window.y, width: window.width, height: y - window.y }, b' ', attr);
+                        console.fill(Rect { x: window.x, y, width: x - window.x + 1, height: 1 }, b' ', attr);
+                    }
+                    // ANSI.SYS also homes the cursor on a full clear
+                    _ => console.clear(),
+                }
+            }
+            // erase in line
+            b'K' => {
+                let (x, y) = (window.x + col, window.y + row);
+                let attr = console.attr;
+                let rect = match self.param(0, 0) {
+                    0 => Rect { x, y, width: window.x + window.width - x, height: 1 },
+                    1 => Rect { x: window.x, y, width: x - window.x + 1, height: 1 },
+                    _ => Rect { x: window.x, y, width: window.width, height: 1 },
+                };
+                console.fill(rect, b' ', attr);
+            }
+            // scroll up, down
+            b'S' => console.scroll_up(self.param(0, 1)),
+            b'T' => console.scroll_down(self.param(0, 1)),
+            b'm' => self.sgr(console),
+            b's' => self.saved_cursor = (col, row),
+            b'u' => console.set_cursor(self.saved_cursor.0, self.saved_cursor.1),
+            // ?25h / ?25l show and hide the cursor
+            b'h' | b'l' if self.private && self.param(0, 0) == 25 => console.show_cursor(command == b'h'),
+            _ => {}
+        }
+    }
+
+    fn sgr(&mut self, console: &mut Console) {
+        // ESC[m is the same as ESC[0m
+        for i in 0..self.param_count.max(1) {
+            match self.params[i] {
+                0 => {
+                    self.fg = 7;
+                    self.bg = 0;
+                    self.bold = false;
+                    self.blink = false;
+                    self.reverse = false;
+                }
+                1 => self.bold = true,
+                5 | 6 => self.blink = true,
+                7 => self.reverse = true,
+                22 => self.bold = false,
+                25 => self.blink = false,
+                27 => self.reverse = false,
+                p @ 30..=37 => self.fg = (p - 30) as u8,
+                39 => self.fg = 7,
+                p @ 40..=47 => self.bg = (p - 40) as u8,
+                49 => self.bg = 0,
+                // the aixterm bright colors
+                p @ 90..=97 => self.fg = (p - 90) as u8 | 8,
+                p @ 100..=107 => self.bg = (p - 100) as u8 | 8,
+                _ => {}
+            }
+        }
+        self.apply_attr(console);
+    }
+
+    fn apply_attr(&self, console: &mut Console) {
+        let mut fg = ANSI_TO_VGA[(self.fg & 7) as usize] | (self.fg & 8);
+        let mut bg = ANSI_TO_VGA[(self.bg & 7) as usize] | (self.bg & 8);
+        if self.bold { fg |= 8; }
+        // without blinking turned off in the VGA, the bright background bit blinks
+        if self.blink { bg |= 8; }
+        if self.reverse { core::mem::swap(&mut fg, &mut bg); }
+        console.attr = Attribute::new(Color::from_u8(fg), Color::from_u8(bg));
+    }
+}
+
+// A text console that understands escape codes written to it
+pub struct AnsiConsole {
+    pub console: Console,
+    pub ansi: Ansi,
+}
+
+impl AnsiConsole {
+    pub fn new(console: Console) -> Self {
+        Self { console, ansi: Ansi::new() }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.ansi.feed_bytes(&mut self.console, bytes);
+        self.console.update_cursor();
+    }
+}
+
+impl Write for AnsiConsole {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            self.ansi.feed(&mut self.console, console::to_cp437(c));
+        }
+        self.console.update_cursor();
+        Ok(())
+    }
+}
+
+// Shows an ANSI art file, stopping at the SAUCE record if it has one
+pub fn display_ans(console: &mut Console, data: &[u8]) {
+    let end = data.iter().position(|&b| b == SUB).unwrap_or(data.len());
+    let mut ansi = Ansi::new();
+    ansi.apply_attr(console);
+    ansi.feed_bytes(console, &data[..end]);
+    console.update_cursor();
+}
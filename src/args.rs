@@ -0,0 +1,155 @@
+#![allow(dead_code)]
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use crate::codepage;
+use crate::dpmi;
+use crate::io::{self, Read};
+
+// response files can name more response files, up to this deep
+const MAX_RESPONSE_DEPTH: usize = 8;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Arg {
+    // anything that isn't a switch, with the quotes taken off
+    Value(String),
+    // /name, -name or --name, optionally followed by :value or =value
+    Switch { name: String, value: Option<String> },
+}
+
+#[derive(Debug)]
+pub enum ArgError {
+    ResponseFile { name: String, error: io::Error },
+    ResponseTooDeep(String),
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ResponseFile { name, error } => write!(f, "Could not read response file {}: {}", name, error),
+            Self::ResponseTooDeep(name) => write!(f, "Response file {} nests too deep", name),
+        }
+    }
+}
+
+// Splits a command line into words at unquoted whitespace
+// Double quotes group words with spaces in them, and "" inside quotes is a literal quote
+// A missing closing quote just runs to the end
+pub fn split(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // a quoted empty string is still a word
+    let mut in_word = false;
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                word.push('"');
+            }
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word { words.push(core::mem::take(&mut word)); }
+                in_word = false;
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word { words.push(word); }
+    words
+}
+
+fn classify(word: String, switches: bool) -> Arg {
+    let name = match word.strip_prefix("--").or_else(|| word.strip_prefix(['/', '-'])) {
+        // a lone - or / is a value, usually meaning stdin or a path
+        Some(name) if switches && !name.is_empty() => name,
+        _ => return Arg::Value(word),
+    };
+    match name.split_once([':', '=']) {
+        Some((name, value)) => Arg::Switch { name: name.into(), value: Some(value.into()) },
+        None => Arg::Switch { name: name.into(), value: None },
+    }
+}
+
+// Response file contents are decoded from the code page like the command line
+fn read_response_file(name: &str) -> Result<String, ArgError> {
+    let error = |error| ArgError::ResponseFile { name: name.into(), error };
+    let mut file = dpmi::File::open(&codepage::to_cstring(name)).map_err(|e| error(e.into()))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).map_err(error)?;
+    Ok(codepage::decode(&data))
+}
+
+// Replaces @name words with the words in that file, newlines count as whitespace
+fn expand_responses(words: Vec<String>, depth: usize, out: &mut Vec<String>) -> Result<(), ArgError> {
+    for word in words {
+        match word.strip_prefix('@').filter(|name| !name.is_empty()) {
+            Some(name) if depth >= MAX_RESPONSE_DEPTH => return Err(ArgError::ResponseTooDeep(name.into())),
+            Some(name) => expand_responses(split(&read_response_file(name)?), depth + 1, out)?,
+            None => out.push(word),
+        }
+    }
+    Ok(())
+}
+
+pub struct Args {
+    pub items: Vec<Arg>,
+}
+
+impl Args {
+    // Parses a command line, reading any @response files it names
+    // Everything after a -- is a value even if it looks like a switch
+    pub fn parse(text: &str) -> Result<Self, ArgError> {
+        let mut words = Vec::new();
+        expand_responses(split(text), 0, &mut words)?;
+        let mut switches = true;
+        let mut items = Vec::new();
+        for word in words {
+            if switches && word == "--" {
+                switches = false;
+                continue;
+            }
+            items.push(classify(word, switches));
+        }
+        Ok(Self { items })
+    }
+
+    // Our own command line, decoded from the DOS code page
+    pub fn from_command_line() -> Result<Self, ArgError> {
+        Self::parse(&codepage::decode(&dpmi::command_tail()))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().filter_map(|item| match item {
+            Arg::Value(value) => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn switches(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.items.iter().filter_map(|item| match item {
+            Arg::Switch { name, value } => Some((name.as_str(), value.as_deref())),
+            _ => None,
+        })
+    }
+
+    // Whether the switch was given, names don't care about case like the rest of DOS
+    pub fn switch(&self, name: &str) -> bool {
+        self.switches().any(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+
+    // The value of the last time the switch was given with one
+    pub fn switch_value(&self, name: &str) -> Option<&str> {
+        self.switches()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .filter_map(|(_, value)| value)
+            .last()
+    }
+}
@@ -1,3 +1,4 @@
+#![allow(dead_code)]
 use core::mem::size_of;
 
 use itertools::Itertools;
@@ -5,8 +6,12 @@ use itertools::Itertools;
 use alloc::boxed::Box;
 use bitvec::{view::BitView, field::BitField};
 
+use crate::io::{self, Read, Write};
 use crate::println;
-use crate::vga::Vga18;
+use crate::vga::{Framebuffer, Vga18};
+
+// anything bigger than this many pixels is taken as a broken file, not something to try allocating
+const MAX_PIXELS: usize = 16 * 1024 * 1024;
 
 #[repr(packed)]
 struct PackedBmpHeader {
@@ -55,77 +60,195 @@ pub struct Bmp {
     pub data: Box<[u8]>
 }
 
-fn load_data(header: &PackedBmpHeader, raw: &[u8]) -> Option<Box<[u8]>> {
-    match header.bpp {
-        8 => {
-	    let row_size = (((8 * header.width + 31) >> 5) << 2) as usize;
-	    let chunks = raw[..row_size * header.height as usize]
-		.chunks_exact(row_size).rev()
-		.flat_map(|f| f[..header.width as usize].iter().cloned());
-	    Some(chunks.collect())
-	},
-	bpp @ (4|2|1) => {
-	    let row_size = ((bpp as usize * header.width as usize + 31) >> 5) << 2;
-	    let chunks = raw[..row_size * header.height as usize]
-		.chunks_exact(row_size).rev()
-		.map(|row| row.view_bits::<bitvec::order::Msb0>()
-		    .chunks(bpp as usize).take(header.width as usize) 
-		    .map(|b| b.load_le::<u8>()));
-	    Some(chunks.flatten().collect())
+// Throws away count bytes, for readers that can't seek
+fn skip(source: &mut impl Read, mut count: usize) -> io::Result<()> {
+    let mut scratch = [0; 256];
+    while count > 0 {
+	let n = count.min(scratch.len());
+	source.read_exact(&mut scratch[..n])?;
+	count -= n;
+    }
+    Ok(())
+}
+
+// Decodes a BMP a row at a time, so only one row of the file is ever in memory
+// Rows come out as 8 bit palette indices, in the order the file stores them
+pub struct BmpDecoder<R: Read> {
+    source: R,
+    pub header: BmpHeader,
+    pub palette_table: alloc::vec::Vec<Vga18>,
+    // a negative height in the file means the rows go top down
+    top_down: bool,
+    // one stored row, padded to 4 bytes
+    row: alloc::vec::Vec<u8>,
+    rows_read: usize,
+}
+
+impl<R: Read> BmpDecoder<R> {
+    // Reads everything up to the pixel data
+    pub fn new(mut source: R) -> io::Result<Self> {
+	// i'm sorry but i love loading file headers like this
+	let mut raw = [0; size_of::<PackedBmpHeader>()];
+	source.read_exact(&mut raw)?;
+	let header: PackedBmpHeader = unsafe { core::mem::transmute(raw) };
+
+	// 'BM'
+	if header.bmp_type != 0x4D42 {
+	    return Err(io::Error::InvalidData("Invalid BMP type"))
 	}
-	bpp => {
-	    println!("Unsupported BPP {}", bpp);
-	    return None
+	if header.header_size < 40 {
+	    return Err(io::Error::InvalidData("Old style BMP headers are not supported"))
 	}
+	if header.compression != 0 {
+	    return Err(io::Error::InvalidData("Compressed BMPs are not supported"))
+	}
+	if !matches!(header.bpp, 8 | 4 | 2 | 1) {
+	    return Err(io::Error::InvalidData("Only 1, 2, 4 and 8 bit BMPs are supported"))
+	}
+	let mut header = BmpHeader::from(header);
+	let top_down = (header.height as i32) < 0;
+	header.height = (header.height as i32).unsigned_abs();
+	// a negative width comes out huge here, and gets caught with the rest
+	let bad_size = || io::Error::InvalidData("Bad BMP dimensions");
+	let pixels = (header.width as usize).checked_mul(header.height as usize).ok_or_else(bad_size)?;
+	if header.width == 0 || pixels > MAX_PIXELS {
+	    return Err(bad_size())
+	}
+	let row_size = (header.bpp as usize).checked_mul(header.width as usize)
+	    .and_then(|bits| bits.checked_add(31))
+	    .map(|bits| (bits >> 5) << 2)
+	    .ok_or_else(bad_size)?;
+
+	// the color table comes right after the info header, 0 colors means all of them
+	skip(&mut source, header.header_size as usize - 40)?;
+	let colors = match header.colors_used {
+	    0 => 1 << header.bpp,
+	    n => n.min(256),
+	} as usize;
+	let mut color_table = alloc::vec![0; colors * 4];
+	source.read_exact(&mut color_table)?;
+	let palette_table = color_table.iter()
+	    .tuples()
+	    .map(|(b,g,r,_)| Vga18 { red:r>>2,green:g>>2,blue:b>>2 })
+	    .collect();
+
+	// the size field is often wrong, so the offset is all we go by
+	let read = 14 + header.header_size as usize + color_table.len();
+	if (header.offset as usize) < read {
+	    return Err(io::Error::InvalidData("BMP bitmap offset inside the header"))
+	}
+	skip(&mut source, header.offset as usize - read)?;
+
+	Ok(Self { source, header, palette_table, top_down, row: alloc::vec![0; row_size], rows_read: 0 })
     }
-}
 
-pub fn load_bmp(source: &[u8]) -> Option<Bmp> {
-    // i'm sorry but i love loading file headers like this
-    let header: PackedBmpHeader = unsafe {
-	let mut copy: [u8; size_of::<PackedBmpHeader>()] = [0; size_of::<PackedBmpHeader>()];
-	copy.copy_from_slice(&source[..size_of::<PackedBmpHeader>()]);
-	core::mem::transmute(copy)
-    };
-    
-    // 'BM'
-    if header.bmp_type != 0x4D42 {
-	println!("Invalid BMP type");
-	return None
+    pub fn width(&self) -> usize {
+	self.header.width as usize
     }
-    if header.size as usize != source.len() {
-	println!("BMP size does not match file length!");
-	return None
+
+    pub fn height(&self) -> usize {
+	self.header.height as usize
     }
-    if header.offset as usize > source.len() {
-	println!("BMP bitmap offset greater than file length!");
-	return None
+
+    // Decodes the next stored row into the first width bytes of dest
+    // Returns which line of the image it was, None when they've all been read
+    pub fn next_row(&mut self, dest: &mut [u8]) -> io::Result<Option<usize>> {
+	if self.rows_read == self.height() { return Ok(None) }
+	self.source.read_exact(&mut self.row)?;
+	let width = self.width();
+	match self.header.bpp {
+	    8 => dest[..width].copy_from_slice(&self.row[..width]),
+	    bpp => {
+		let pixels = self.row.view_bits::<bitvec::order::Msb0>()
+		    .chunks(bpp as usize).take(width)
+		    .map(|b| b.load_le::<u8>());
+		for (d, p) in dest[..width].iter_mut().zip(pixels) {
+		    *d = p;
+		}
+	    }
+	}
+	let line = if self.top_down { self.rows_read } else { self.height() - 1 - self.rows_read };
+	self.rows_read += 1;
+	Ok(Some(line))
     }
-    if header.compression != 0 {
-	println!("Compressed BMPs are not supported");
-	return None
+
+    // Decodes the rest of the image into dest, width * height bytes top row first
+    pub fn decode_into(&mut self, dest: &mut [u8]) -> io::Result<()> {
+	let width = self.width();
+	let mut row = alloc::vec![0; width];
+	while let Some(line) = self.next_row(&mut row)? {
+	    dest[line * width..][..width].copy_from_slice(&row);
+	}
+	Ok(())
     }
-    if {
-	let row_size = ((header.bpp as usize * header.width as usize + 31) >> 5) << 2;
-	let (calc_size, overflow) = row_size.overflowing_mul(header.height as usize);
-	(calc_size > (source.len() - header.offset as usize)) | overflow
-    } {
-	println!("File cannot possibly contain full BMP data!");
-	return None
+
+    // Decodes the rest of the image straight onto a framebuffer, top left corner at x, y
+    // Anything off the edges gets clipped by the framebuffer
+    pub fn draw(&mut self, fb: &mut dyn Framebuffer, x: isize, y: isize) -> io::Result<()> {
+	let mut row = alloc::vec![0; self.width()];
+	while let Some(line) = self.next_row(&mut row)? {
+	    for (col, &color) in row.iter().enumerate() {
+		fb.put_pixel(x + col as isize, y + line as isize, color);
+	    }
+	}
+	Ok(())
     }
+}
 
-    let color_table = &source[(header.offset - header.colors_used * 4) as usize..];
-    let palette: alloc::vec::Vec<_> = color_table.iter()
-	.tuples().take(header.colors_used as usize)
-	.map(|(b,g,r,_)| Vga18 { red:r>>2,green:g>>2,blue:b>>2 })
-	.collect();
+// Reads a whole BMP from any reader
+pub fn read_bmp(source: &mut impl Read) -> io::Result<Bmp> {
+    let mut decoder = BmpDecoder::new(source)?;
+    let mut data = alloc::vec![0; decoder.width() * decoder.height()].into_boxed_slice();
+    decoder.decode_into(&mut data)?;
+    Ok(Bmp { header: decoder.header, palette_table: decoder.palette_table, data })
+}
 
-    let raw_data = &source[header.offset as usize..];
-    let data = load_data(&header, raw_data);
+// Same, but says what went wrong
+pub fn load_bmp(source: &mut impl Read) -> Option<Bmp> {
+    match read_bmp(source) {
+	Ok(bmp) => Some(bmp),
+	Err(e) => {
+	    println!("Could not load BMP: {}", e);
+	    None
+	}
+    }
+}
 
-    Some(Bmp {
-        header: BmpHeader::from(header), 
-        palette_table: palette,
-        data: data?
-    })
+// Writes an uncompressed 8 bit BMP, pixels go top row first like on screen
+pub fn save_bmp(out: &mut impl Write, width: u32, height: u32, pixels: &[u8], palette: &[Vga18]) -> io::Result<()> {
+    let row_size = (((8 * width + 31) >> 5) << 2) as usize;
+    let offset = (size_of::<PackedBmpHeader>() + palette.len() * 4) as u32;
+    let header = PackedBmpHeader {
+	bmp_type: 0x4D42,
+	size: offset + (row_size * height as usize) as u32,
+	reserved: 0,
+	offset,
+	// BITMAPINFOHEADER, the part after the file header
+	header_size: 40,
+	width,
+	height,
+	planes: 1,
+	bpp: 8,
+	compression: 0,
+	size_image: (row_size * height as usize) as u32,
+	xppm: 0,
+	yppm: 0,
+	colors_used: palette.len() as u32,
+	colors_important: 0,
+    };
+    let header: [u8; size_of::<PackedBmpHeader>()] = unsafe { core::mem::transmute(header) };
+    out.write_all(&header)?;
+
+    let colors: alloc::vec::Vec<u8> = palette.iter()
+	.flat_map(|c| [c.blue << 2, c.green << 2, c.red << 2, 0])
+	.collect();
+    out.write_all(&colors)?;
+
+    // rows are stored bottom up, padded to 4 bytes
+    let mut row = alloc::vec![0; row_size];
+    for line in pixels.chunks_exact(width as usize).take(height as usize).rev() {
+	row[..width as usize].copy_from_slice(line);
+	out.write_all(&row)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,158 @@
+#![allow(dead_code)]
+use alloc::{ffi::CString, string::String, vec::Vec};
+use core::arch::asm;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+// The DOS code pages we can convert to and from
+// The bottom halves are ASCII, the tables are the top halves
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CodePage {
+    // the original IBM PC one, and what the VGA ROM font has
+    Cp437,
+    // Western European, trades most of the box drawing for accented capitals
+    Cp850,
+}
+
+const CP437: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+const CP850: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', 'ø', '£', 'Ø', '×', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '®', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', 'Á', 'Â', 'À', '©', '╣', '║', '╗', '╝', '¢', '¥', '┐',
+    '└', '┴', '┬', '├', '─', '┼', 'ã', 'Ã', '╚', '╔', '╩', '╦', '╠', '═', '╬', '¤',
+    'ð', 'Ð', 'Ê', 'Ë', 'È', 'ı', 'Í', 'Î', 'Ï', '┘', '┌', '█', '▄', '¦', 'Ì', '▀',
+    'Ó', 'ß', 'Ô', 'Ò', 'õ', 'Õ', 'µ', 'þ', 'Þ', 'Ú', 'Û', 'Ù', 'ý', 'Ý', '¯', '´',
+    '\u{ad}', '±', '‗', '¾', '¶', '§', '÷', '¸', '°', '¨', '·', '¹', '³', '²', '■', '\u{a0}',
+];
+
+// what the font shows for control characters 01h-1Fh, the same in both
+const CONTROL_GLYPHS: [char; 31] = [
+    '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼', '►',
+    '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+];
+// and for 7Fh
+const HOUSE: char = '⌂';
+
+impl CodePage {
+    pub fn number(self) -> u16 {
+        match self {
+            Self::Cp437 => 437,
+            Self::Cp850 => 850,
+        }
+    }
+
+    pub fn from_number(number: u16) -> Option<Self> {
+        match number {
+            437 => Some(Self::Cp437),
+            850 => Some(Self::Cp850),
+            _ => None,
+        }
+    }
+
+    fn high(self) -> &'static [char; 128] {
+        match self {
+            Self::Cp437 => &CP437,
+            Self::Cp850 => &CP850,
+        }
+    }
+
+    // Control characters stay control characters, so text decodes as text
+    pub fn decode_byte(self, byte: u8) -> char {
+        if byte < 0x80 { byte as char } else { self.high()[byte as usize - 0x80] }
+    }
+
+    // Anything the code page doesn't have turns into ?
+    // The control character glyphs map back to their bytes, so they can be drawn
+    pub fn encode_char(self, c: char) -> u8 {
+        if c.is_ascii() { return c as u8 }
+        if c == HOUSE { return 0x7f }
+        if let Some(i) = self.high().iter().position(|&h| h == c) {
+            return 0x80 + i as u8;
+        }
+        match CONTROL_GLYPHS.iter().position(|&g| g == c) {
+            Some(i) => 1 + i as u8,
+            None => b'?',
+        }
+    }
+
+    // Every byte is some character, so this can't fail
+    pub fn decode(self, bytes: &[u8]) -> String {
+        bytes.iter().map(|&b| self.decode_byte(b)).collect()
+    }
+
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        text.chars().map(|c| self.encode_char(c)).collect()
+    }
+}
+
+// 0 until DOS has been asked, then 1 + the index of the code page
+static ACTIVE: AtomicU8 = AtomicU8::new(0);
+
+// The code page DOS is using, for text going to and coming from DOS
+// Anything we don't have a table for gets treated as 437
+pub fn active() -> CodePage {
+    match ACTIVE.load(Ordering::Relaxed) {
+        1 => CodePage::Cp437,
+        2 => CodePage::Cp850,
+        _ => {
+            // DOS function 21h, AX 6601h - Get Global Code Page
+            // Out:
+            //     if successful:
+            //     CF clear
+            //     BX = active code page
+            //     DX = system code page
+            //
+            //     if failed:
+            //     CF set (DOS before 3.3)
+            let ebx: u32;
+            let err: u8;
+            unsafe { asm!(
+                "int 0x21",
+                "setc cl",
+                inout("eax") 0x6601_u32 => _,
+                out("ebx") ebx,
+                out("cl") err,
+                out("edx") _,
+            ); }
+            let page = match err {
+                0 => CodePage::from_number(ebx as u16).unwrap_or(CodePage::Cp437),
+                _ => CodePage::Cp437,
+            };
+            set_active(page);
+            page
+        }
+    }
+}
+
+// For when something else knows better, like a keyboard layout that goes with a code page
+pub fn set_active(page: CodePage) {
+    ACTIVE.store(match page { CodePage::Cp437 => 1, CodePage::Cp850 => 2 }, Ordering::Relaxed);
+}
+
+pub fn decode(bytes: &[u8]) -> String {
+    active().decode(bytes)
+}
+
+pub fn encode(text: &str) -> Vec<u8> {
+    active().encode(text)
+}
+
+// A path or other string for DOS, in its code page
+// Stops at a 0, since DOS would anyway
+pub fn to_cstring(text: &str) -> CString {
+    let mut bytes = encode(text);
+    if let Some(end) = bytes.iter().position(|&b| b == 0) {
+        bytes.truncate(end);
+    }
+    CString::new(bytes).unwrap()
+}
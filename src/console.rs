@@ -0,0 +1,383 @@
+#![allow(dead_code)]
+use core::fmt::{Arguments, Write};
+
+use crate::ansi::AnsiConsole;
+use crate::codepage::CodePage;
+use crate::dpmi::{self, DpmiRegs};
+use crate::vga::outb;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Color {
+    Black = 0,
+    Blue,
+    Green,
+    Cyan,
+    Red,
+    Magenta,
+    Brown,
+    LightGray,
+    DarkGray,
+    LightBlue,
+    LightGreen,
+    LightCyan,
+    LightRed,
+    LightMagenta,
+    Yellow,
+    White,
+}
+
+impl Color {
+    pub fn from_u8(value: u8) -> Self {
+        // all 16 values are valid colors
+        unsafe { core::mem::transmute(value & 0x0F) }
+    }
+}
+
+// A text mode attribute byte, background in the high nibble
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Attribute(pub u8);
+
+impl Attribute {
+    pub const fn new(fg: Color, bg: Color) -> Self {
+        Self((bg as u8) << 4 | fg as u8)
+    }
+    pub fn fg(self) -> Color { Color::from_u8(self.0) }
+    pub fn bg(self) -> Color { Color::from_u8(self.0 >> 4) }
+    pub fn with_fg(self, fg: Color) -> Self { Self(self.0 & 0xF0 | fg as u8) }
+    pub fn with_bg(self, bg: Color) -> Self { Self(self.0 & 0x0F | (bg as u8) << 4) }
+}
+
+impl Default for Attribute {
+    fn default() -> Self { Self::new(Color::LightGray, Color::Black) }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum BoxStyle {
+    Single,
+    Double,
+}
+
+impl BoxStyle {
+    // CP437 corners and lines: top left, top right, bottom left, bottom right, horizontal, vertical
+    fn chars(self) -> [u8; 6] {
+        match self {
+            BoxStyle::Single => [0xDA, 0xBF, 0xC0, 0xD9, 0xC4, 0xB3],
+            BoxStyle::Double => [0xC9, 0xBB, 0xC8, 0xBC, 0xCD, 0xBA],
+        }
+    }
+}
+
+// CRTC registers, the color text mode base is 0x3D4
+const CRTC_INDEX: u16 = 0x3d4;
+const CRTC_DATA: u16 = 0x3d5;
+const CRTC_CURSOR_START: u8 = 0x0a;
+const CRTC_CURSOR_END: u8 = 0x0b;
+const CRTC_CURSOR_HIGH: u8 = 0x0e;
+const CRTC_CURSOR_LOW: u8 = 0x0f;
+
+fn crtc_write(index: u8, data: u8) {
+    unsafe {
+        outb(CRTC_INDEX, index);
+        outb(CRTC_DATA, data);
+    }
+}
+
+// Direct to VRAM text console at 0xB8000
+pub struct Console {
+    vram: &'static mut [u16],
+    pub width: usize,
+    pub height: usize,
+    // output is confined to this part of the screen, and it scrolls on its own
+    window: Rect,
+    // cursor position is relative to the window
+    col: usize,
+    row: usize,
+    pub attr: Attribute,
+    cursor_visible: bool,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        // columns and rows come from the BIOS data area, rows is 0 on pre-EGA BIOSes
+        let width = unsafe { *(0x44a as *const u16) } as usize;
+        let rows = unsafe { *(0x484 as *const u8) } as usize;
+        let height = if rows == 0 { 25 } else { rows + 1 };
+        let vram = unsafe { core::slice::from_raw_parts_mut(0xb8000 as *mut u16, width * height) };
+
+        // pick up where DOS left off, page 0 cursor is at 0x450
+        let (col, row) = unsafe { (*(0x450 as *const u8) as usize, *(0x451 as *const u8) as usize) };
+
+        Self {
+            vram,
+            width,
+            height,
+            window: Rect { x: 0, y: 0, width, height },
+            col: col.min(width - 1),
+            row: row.min(height - 1),
+            attr: Attribute::default(),
+            cursor_visible: true,
+        }
+    }
+
+    pub fn window(&self) -> Rect { self.window }
+
+    // Confines output to a part of the screen, the cursor moves to its top left
+    pub fn set_window(&mut self, window: Rect) {
+        let x = window.x.min(self.width - 1);
+        let y = window.y.min(self.height - 1);
+        self.window = Rect {
+            x,
+            y,
+            width: window.width.clamp(1, self.width - x),
+            height: window.height.clamp(1, self.height - y),
+        };
+        self.set_cursor(0, 0);
+    }
+
+    pub fn reset_window(&mut self) {
+        self.set_window(Rect { x: 0, y: 0, width: self.width, height: self.height });
+    }
+
+    pub fn cursor(&self) -> (usize, usize) { (self.col, self.row) }
+
+    pub fn set_cursor(&mut self, col: usize, row: usize) {
+        self.col = col.min(self.window.width - 1);
+        self.row = row.min(self.window.height - 1);
+        self.update_cursor();
+    }
+
+    pub fn show_cursor(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+        if visible {
+            // underline cursor on the last two scanlines of a 16 line cell
+            crtc_write(CRTC_CURSOR_START, 0x0d);
+            crtc_write(CRTC_CURSOR_END, 0x0e);
+        } else {
+            // bit 5 turns the cursor off
+            crtc_write(CRTC_CURSOR_START, 0x20);
+        }
+    }
+
+    pub(crate) fn update_cursor(&self) {
+        let pos = (self.window.y + self.row) * self.width + self.window.x + self.col;
+        crtc_write(CRTC_CURSOR_HIGH, (pos >> 8) as u8);
+        crtc_write(CRTC_CURSOR_LOW, pos as u8);
+    }
+
+    // BIOS function INT 10,2 Set cursor position
+    // tells the BIOS (and so DOS) where our cursor ended up
+    pub fn sync_bios_cursor(&self) {
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x0200;
+        regs.edx = (((self.window.y + self.row) << 8) | (self.window.x + self.col)) as u32;
+        dpmi::real_int(0x10, &mut regs);
+    }
+
+    // Writes a character cell at an absolute screen position, doesn't move the cursor
+    pub fn put(&mut self, x: usize, y: usize, ch: u8, attr: Attribute) {
+        if x < self.width && y < self.height {
+            self.vram[y * self.width + x] = (attr.0 as u16) << 8 | ch as u16;
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> (u8, Attribute) {
+        let cell = self.vram[y * self.width + x];
+        (cell as u8, Attribute((cell >> 8) as u8))
+    }
+
+    pub fn put_str(&mut self, x: usize, y: usize, text: &str, attr: Attribute) {
+        for (i, ch) in text.chars().enumerate() {
+            self.put(x + i, y, to_cp437(ch), attr);
+        }
+    }
+
+    pub fn fill(&mut self, rect: Rect, ch: u8, attr: Attribute) {
+        for y in rect.y..(rect.y + rect.height).min(self.height) {
+            for x in rect.x..(rect.x + rect.width).min(self.width) {
+                self.put(x, y, ch, attr);
+            }
+        }
+    }
+
+    // Clears the current window and homes the cursor
+    pub fn clear(&mut self) {
+        self.fill(self.window, b' ', self.attr);
+        self.set_cursor(0, 0);
+    }
+
+    pub fn clear_line(&mut self) {
+        let rect = Rect { x: self.window.x, y: self.window.y + self.row, width: self.window.width, height: 1 };
+        self.fill(rect, b' ', self.attr);
+    }
+
+    // Scrolls the window up by `lines`, the bottom is filled with blanks
+    pub fn scroll_up(&mut self, lines: usize) {
+        let Rect { x, y, width, height } = self.window;
+        let lines = lines.min(height);
+        for row in y..y + height - lines {
+            let dst = row * self.width + x;
+            let src = (row + lines) * self.width + x;
+            self.vram.copy_within(src..src + width, dst);
+        }
+        self.fill(Rect { x, y: y + height - lines, width, height: lines }, b' ', self.attr);
+    }
+
+    // Scrolls the window down by `lines`, the top is filled with blanks
+    pub fn scroll_down(&mut self, lines: usize) {
+        let Rect { x, y, width, height } = self.window;
+        let lines = lines.min(height);
+        for row in (y + lines..y + height).rev() {
+            let dst = row * self.width + x;
+            let src = (row - lines) * self.width + x;
+            self.vram.copy_within(src..src + width, dst);
+        }
+        self.fill(Rect { x, y, width, height: lines }, b' ', self.attr);
+    }
+
+    fn new_line(&mut self) {
+        self.col = 0;
+        if self.row + 1 >= self.window.height {
+            self.scroll_up(1);
+        } else {
+            self.row += 1;
+        }
+    }
+
+    // Writes a raw CP437 byte at the cursor, handling the usual control characters
+    pub fn write_byte(&mut self, byte: u8) {
+        match byte {
+            b'\r' => self.col = 0,
+            b'\n' => self.new_line(),
+            b'\t' => {
+                let next = (self.col + 8) & !7;
+                while self.col < next && self.col < self.window.width {
+                    self.write_byte(b' ');
+                }
+            }
+            0x08 => self.col = self.col.saturating_sub(1),
+            0x07 => {}
+            _ => {
+                if self.col >= self.window.width {
+                    self.new_line();
+                }
+                let (x, y) = (self.window.x + self.col, self.window.y + self.row);
+                self.put(x, y, byte, self.attr);
+                self.col += 1;
+            }
+        }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_byte(b);
+        }
+        self.update_cursor();
+    }
+
+    pub fn draw_box(&mut self, rect: Rect, style: BoxStyle, attr: Attribute) {
+        if rect.width < 2 || rect.height < 2 { return }
+        let [tl, tr, bl, br, h, v] = style.chars();
+        let (right, bottom) = (rect.x + rect.width - 1, rect.y + rect.height - 1);
+        for x in rect.x + 1..right {
+            self.put(x, rect.y, h, attr);
+            self.put(x, bottom, h, attr);
+        }
+        for y in rect.y + 1..bottom {
+            self.put(rect.x, y, v, attr);
+            self.put(right, y, v, attr);
+        }
+        self.put(rect.x, rect.y, tl, attr);
+        self.put(right, rect.y, tr, attr);
+        self.put(rect.x, bottom, bl, attr);
+        self.put(right, bottom, br, attr);
+    }
+
+    // Draws a framed, cleared box with an optional title and makes its inside the window
+    pub fn open_window(&mut self, rect: Rect, title: Option<&str>, style: BoxStyle) {
+        self.fill(rect, b' ', self.attr);
+        self.draw_box(rect, style, self.attr);
+        if let Some(title) = title {
+            let len = title.chars().count().min(rect.width.saturating_sub(4));
+            let x = rect.x + (rect.width - len) / 2;
+            let title: alloc::string::String = title.chars().take(len).collect();
+            self.put_str(x, rect.y, &title, self.attr);
+        }
+        self.set_window(Rect {
+            x: rect.x + 1,
+            y: rect.y + 1,
+            width: rect.width.saturating_sub(2),
+            height: rect.height.saturating_sub(2),
+        });
+    }
+}
+
+impl Default for Console {
+    fn default() -> Self { Self::new() }
+}
+
+impl Drop for Console {
+    fn drop(&mut self) {
+        self.sync_bios_cursor();
+    }
+}
+
+// The VGA font is in code page 437, anything it doesn't have shows as ?
+pub(crate) fn to_cp437(c: char) -> u8 {
+    CodePage::Cp437.encode_char(c)
+}
+
+impl Write for Console {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            self.write_byte(to_cp437(c));
+        }
+        self.update_cursor();
+        Ok(())
+    }
+}
+
+// print! output goes through the ANSI interpreter, so escape codes work without ANSI.SYS
+static PRINT_CONSOLE: spin::Mutex<Option<AnsiConsole>> = spin::Mutex::new(None);
+
+// Sends print!/println! to `console` instead of DOS, or back to DOS with None
+// Returns the console that was previously used
+pub fn redirect_print(console: Option<Console>) -> Option<Console> {
+    let old = core::mem::replace(&mut *PRINT_CONSOLE.lock(), console.map(AnsiConsole::new));
+    old.map(|c| c.console)
+}
+
+// Runs `f` on the console print! is going to, if there is one
+pub fn with_print_console<R>(f: impl FnOnce(&mut Console) -> R) -> Option<R> {
+    PRINT_CONSOLE.lock().as_mut().map(|c| f(&mut c.console))
+}
+
+// Takes the print console out for `f`, print! goes to DOS meanwhile
+// For things that hold on to the console for a while, like dialogs, so a print or panic inside doesn't wait on the lock
+pub fn with_console_taken<R>(f: impl FnOnce(&mut Console) -> R) -> Option<R> {
+    let mut console = redirect_print(None)?;
+    let result = f(&mut console);
+    redirect_print(Some(console));
+    Some(result)
+}
+
+#[doc(hidden)]
+pub fn _print(args: Arguments) -> bool {
+    // if the console is busy (a panic inside with_print_console) let the caller go through DOS
+    let Some(mut console) = PRINT_CONSOLE.try_lock() else { return false };
+    match console.as_mut() {
+        Some(console) => {
+            console.write_fmt(args).unwrap();
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,188 @@
+#![allow(dead_code)]
+use alloc::{vec, vec::Vec};
+
+use crate::vga::Framebuffer;
+
+// A masked image, pixels that are None let the background through
+pub struct Sprite {
+    pub width: usize,
+    pub height: usize,
+    // the pixel that sits on the cursor position
+    pub hot_x: isize,
+    pub hot_y: isize,
+    pixels: Vec<Option<u8>>,
+}
+
+const ARROW: [&str; 16] = [
+    "X          ",
+    "XX         ",
+    "X.X        ",
+    "X..X       ",
+    "X...X      ",
+    "X....X     ",
+    "X.....X    ",
+    "X......X   ",
+    "X.......X  ",
+    "X........X ",
+    "X.....XXXXX",
+    "X..X..X    ",
+    "X.X X..X   ",
+    "XX  X..X   ",
+    "X    X..X  ",
+    "     XXXX  ",
+];
+
+impl Sprite {
+    pub fn new(width: usize, height: usize, hot_x: isize, hot_y: isize, pixels: Vec<Option<u8>>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        Self { width, height, hot_x, hot_y, pixels }
+    }
+
+    // One string per row, characters listed in colors are drawn in that color
+    // and anything else is transparent
+    pub fn from_pattern(rows: &[&str], hot_x: isize, hot_y: isize, colors: &[(char, u8)]) -> Self {
+        let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+        let mut pixels = vec![None; width * rows.len()];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                pixels[y * width + x] = colors.iter().find(|&&(k, _)| k == c).map(|&(_, color)| color);
+            }
+        }
+        Self::new(width, rows.len(), hot_x, hot_y, pixels)
+    }
+
+    // The usual arrow pointer, hot spot at the tip
+    pub fn arrow(outline: u8, fill: u8) -> Self {
+        Self::from_pattern(&ARROW, 0, 0, &[('X', outline), ('.', fill)])
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Option<u8> {
+        if x >= self.width || y >= self.height { return None }
+        self.pixels[y * self.width + x]
+    }
+}
+
+// A pointer drawn by us instead of the mouse driver, so it works with any Framebuffer
+// It keeps a copy of what it covers, to put back when it moves or hides
+pub struct SoftwareCursor {
+    sprite: Sprite,
+    x: isize,
+    y: isize,
+    // top left corner of where the sprite is drawn right now, if it is
+    drawn: Option<(isize, isize)>,
+    // the background under the drawn sprite
+    saved: Vec<u8>,
+}
+
+impl SoftwareCursor {
+    pub fn new(sprite: Sprite) -> Self {
+        let saved = vec![0; sprite.width * sprite.height];
+        Self { sprite, x: 0, y: 0, drawn: None, saved }
+    }
+
+    pub fn position(&self) -> (isize, isize) {
+        (self.x, self.y)
+    }
+
+    pub fn visible(&self) -> bool {
+        self.drawn.is_some()
+    }
+
+    fn top_left(&self) -> (isize, isize) {
+        (self.x - self.sprite.hot_x, self.y - self.sprite.hot_y)
+    }
+
+    pub fn show(&mut self, fb: &mut dyn Framebuffer) {
+        if self.drawn.is_some() { return }
+        let (left, top) = self.top_left();
+        let (width, height) = (self.sprite.width, self.sprite.height);
+        for row in 0..height {
+            for col in 0..width {
+                let (x, y) = (left + col as isize, top + row as isize);
+                self.saved[row * width + col] = fb.get_pixel(x, y);
+                if let Some(color) = self.sprite.pixel(col, row) {
+                    fb.put_pixel(x, y, color);
+                }
+            }
+        }
+        self.drawn = Some((left, top));
+    }
+
+    pub fn hide(&mut self, fb: &mut dyn Framebuffer) {
+        self.restore_background(fb);
+        self.drawn = None;
+    }
+
+    // Puts back what the cursor covers on `fb` but still counts it as shown,
+    // for taking it out of a back buffer once that's been copied to the screen with it
+    pub fn restore_background(&self, fb: &mut dyn Framebuffer) {
+        let (left, top) = match self.drawn {
+            Some(corner) => corner,
+            None => return,
+        };
+        let width = self.sprite.width;
+        for row in 0..self.sprite.height {
+            for col in 0..width {
+                fb.put_pixel(left + col as isize, top + row as isize, self.saved[row * width + col]);
+            }
+        }
+    }
+
+    // For when whatever was under the cursor has been redrawn anyway,
+    // the next show saves the new background instead of putting back the old one
+    pub fn forget(&mut self) {
+        self.drawn = None;
+    }
+
+    // Moves the cursor, redrawing it if it's shown
+    // The old and new spots are put together off screen first and written once,
+    // so no pixel ever shows the background where the cursor should be
+    pub fn move_to(&mut self, fb: &mut dyn Framebuffer, x: isize, y: isize) {
+        if (x, y) == (self.x, self.y) { return }
+        self.x = x;
+        self.y = y;
+        let (old_left, old_top) = match self.drawn {
+            Some(corner) => corner,
+            None => return,
+        };
+        let (new_left, new_top) = self.top_left();
+        let (width, height) = (self.sprite.width as isize, self.sprite.height as isize);
+
+        let left = old_left.min(new_left);
+        let top = old_top.min(new_top);
+        let area_width = ((old_left.max(new_left) + width) - left) as usize;
+        let area_height = ((old_top.max(new_top) + height) - top) as usize;
+        let inside = |x: isize, y: isize, l: isize, t: isize| (l..l + width).contains(&x) && (t..t + height).contains(&y);
+
+        // the background of the whole area, with the old cursor taken out
+        let mut area = vec![0; area_width * area_height];
+        for row in 0..area_height {
+            for col in 0..area_width {
+                let (x, y) = (left + col as isize, top + row as isize);
+                area[row * area_width + col] = if inside(x, y, old_left, old_top) {
+                    self.saved[((y - old_top) * width + (x - old_left)) as usize]
+                } else {
+                    fb.get_pixel(x, y)
+                };
+            }
+        }
+
+        // save what's under the new spot and draw the sprite over it
+        for row in 0..self.sprite.height {
+            for col in 0..self.sprite.width {
+                let index = (new_top - top) as usize * area_width + row * area_width + (new_left - left) as usize + col;
+                self.saved[row * self.sprite.width + col] = area[index];
+                if let Some(color) = self.sprite.pixel(col, row) {
+                    area[index] = color;
+                }
+            }
+        }
+
+        for row in 0..area_height {
+            for col in 0..area_width {
+                fb.put_pixel(left + col as isize, top + row as isize, area[row * area_width + col]);
+            }
+        }
+        self.drawn = Some((new_left, new_top));
+    }
+}
@@ -1,5 +1,5 @@
 #![allow(dead_code)]
-use core::{arch::asm, fmt::{Arguments, Write}, ffi::CStr, str::FromStr};
+use core::{arch::asm, fmt::{Arguments, Write}, ffi::CStr};
 
 extern crate alloc;
 
@@ -40,6 +40,8 @@ pub fn set_video_mode(mode: u8) {
 
 // BIOS function INT 16,0 Wait for keystroke and read
 pub fn getchar() -> u16 {
+    // whatever we're prompting with has to be out first
+    flush_stdout();
     let mut regs = DpmiRegs::zero();
     real_int(0x16, &mut regs);
     regs.eax as u16
@@ -59,6 +61,90 @@ pub fn kb_status() -> Option<u16> {
     }
 }
 
+// A key from the enhanced keyboard functions
+// ascii is 0 (or E0h for the keys only enhanced keyboards have) for keys without a character
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct BiosKey {
+    pub scan: u8,
+    pub ascii: u8,
+}
+
+impl BiosKey {
+    fn from_ax(ax: u16) -> Self {
+        Self { scan: (ax >> 8) as u8, ascii: ax as u8 }
+    }
+
+    // arrows, function keys and the like
+    pub fn is_extended(self) -> bool {
+        self.ascii == 0 || (self.ascii == 0xE0 && self.scan != 0)
+    }
+}
+
+// BIOS function INT 16,10 Wait for keystroke and read, enhanced keyboard
+// Unlike AH=0 this doesn't throw away F11, F12 and the grey keys
+pub fn read_key() -> BiosKey {
+    flush_stdout();
+    let mut regs = DpmiRegs::zero();
+    regs.eax = 0x1000;
+    real_int(0x16, &mut regs);
+    BiosKey::from_ax(regs.eax as u16)
+}
+
+// BIOS function INT 16,11 Get keyboard status, enhanced keyboard
+// The key stays in the buffer
+pub fn peek_key() -> Option<BiosKey> {
+    let mut regs = DpmiRegs::zero();
+    regs.eax = 0x1100;
+    real_int(0x16, &mut regs);
+    // ZF set means there's nothing
+    match regs.status_flags & 0x40 {
+        0 => Some(BiosKey::from_ax(regs.eax as u16)),
+        _ => None,
+    }
+}
+
+// Shift, lock and other modifier keys, as INT 16h AH=12h has them
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct ShiftState(pub u16);
+
+impl ShiftState {
+    pub const RIGHT_SHIFT: u16 = 0x0001;
+    pub const LEFT_SHIFT: u16 = 0x0002;
+    // either one
+    pub const CTRL: u16 = 0x0004;
+    pub const ALT: u16 = 0x0008;
+    // the lock states, then whether they're active
+    pub const SCROLL_LOCK: u16 = 0x0010;
+    pub const NUM_LOCK: u16 = 0x0020;
+    pub const CAPS_LOCK: u16 = 0x0040;
+    pub const INSERT: u16 = 0x0080;
+    // which ones are held down
+    pub const LEFT_CTRL: u16 = 0x0100;
+    pub const LEFT_ALT: u16 = 0x0200;
+    pub const RIGHT_CTRL: u16 = 0x0400;
+    pub const RIGHT_ALT: u16 = 0x0800;
+    pub const SCROLL_LOCK_DOWN: u16 = 0x1000;
+    pub const NUM_LOCK_DOWN: u16 = 0x2000;
+    pub const CAPS_LOCK_DOWN: u16 = 0x4000;
+    pub const SYSRQ_DOWN: u16 = 0x8000;
+
+    pub fn contains(self, bits: u16) -> bool {
+        self.0 & bits == bits
+    }
+
+    pub fn shift(self) -> bool {
+        self.0 & (Self::LEFT_SHIFT | Self::RIGHT_SHIFT) != 0
+    }
+}
+
+// BIOS function INT 16,12 Get extended shift states
+pub fn shift_state() -> ShiftState {
+    let mut regs = DpmiRegs::zero();
+    regs.eax = 0x1200;
+    real_int(0x16, &mut regs);
+    ShiftState(regs.eax as u16)
+}
+
 pub fn get_psp(buff: &mut [u8; 256]) {
     // DOS DPMI function 21h,AH 62h - Get PSP Selector
     // Out: EBX = PSP selector
@@ -81,25 +167,287 @@ pub fn get_psp(buff: &mut [u8; 256]) {
     ); }
 }
 
-pub fn get_args() -> alloc::vec::Vec<alloc::string::String> {
+// A copy of our environment block, the NAME=value strings then the program path
+// DOS/32A swaps the segment at PSP offset 2Ch for a selector, so it gets read through that
+pub fn environment_block() -> alloc::vec::Vec<u8> {
+    let mut buff = [0_u8; 256];
+    get_psp(&mut buff);
+    let selector = u16::from_le_bytes([buff[0x2C], buff[0x2D]]);
+    if selector == 0 { return alloc::vec::Vec::new() }
+
+    // the selector's limit is as big as the block can be, DOS caps it at 32K
+    let limit: u32;
+    let valid: u8;
+    unsafe { asm!(
+        "lsl {limit}, {selector:e}",
+        "setz {valid}",
+        selector = in(reg) selector as u32,
+        limit = out(reg) limit,
+        valid = out(reg_byte) valid,
+    ); }
+    if valid == 0 { return alloc::vec::Vec::new() }
+    let size = (limit as usize + 1).min(0x8000);
+
+    let mut block = alloc::vec![0_u8; size];
+    unsafe { asm!(
+        "push esi",
+        "mov ds, ax",
+        "xor esi, esi",
+        "rep movsb",
+        "push es",
+        "pop ds",
+        "pop esi",
+        in("ax") selector,
+        inout("ecx") size => _,
+        inout("edi") block.as_mut_ptr() => _,
+    ); }
+    block
+}
+
+// The command line as DOS hands it over in the PSP, at most 127 bytes
+pub fn command_tail() -> alloc::vec::Vec<u8> {
     let mut buff = [0_u8; 256];
     get_psp(&mut buff);
     let cmd_byte_cnt = buff[0x80];
     let cmd_line = &buff[0x81..0x100.min(0x81+cmd_byte_cnt as usize)];
-    // this is a horrible way of doing this, but i'm lazy
-    core::str::from_utf8(cmd_line).unwrap()
-        .split_whitespace()
-        .map(|s| alloc::string::String::from_str(s).unwrap())
-        .collect()
+    // it ends with a CR that the count doesn't include, but not every loader does that
+    let end = cmd_line.iter().position(|&b| b == b'\r').unwrap_or(cmd_line.len());
+    cmd_line[..end].to_vec()
 }
 
+// INT 21h error codes, what comes back in AX with CF set
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DosError {
+    InvalidFunction,
+    FileNotFound,
+    PathNotFound,
+    TooManyOpenFiles,
+    AccessDenied,
+    InvalidHandle,
+    MemoryBlocksDestroyed,
+    NotEnoughMemory,
+    InvalidMemoryBlock,
+    InvalidEnvironment,
+    InvalidFormat,
+    InvalidAccessMode,
+    InvalidData,
+    InvalidDrive,
+    RemoveCurrentDirectory,
+    NotSameDevice,
+    NoMoreFiles,
+    WriteProtected,
+    UnknownUnit,
+    DriveNotReady,
+    UnknownCommand,
+    CrcError,
+    BadRequestLength,
+    SeekError,
+    UnknownMedia,
+    SectorNotFound,
+    OutOfPaper,
+    WriteFault,
+    ReadFault,
+    GeneralFailure,
+    SharingViolation,
+    LockViolation,
+    InvalidDiskChange,
+    FcbUnavailable,
+    SharingBufferOverflow,
+    DiskFull,
+    FileExists,
+    CannotMakeDirectory,
+    CriticalError,
+    Other(u16),
+}
+
+impl DosError {
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            0x01 => Self::InvalidFunction,
+            0x02 => Self::FileNotFound,
+            0x03 => Self::PathNotFound,
+            0x04 => Self::TooManyOpenFiles,
+            0x05 => Self::AccessDenied,
+            0x06 => Self::InvalidHandle,
+            0x07 => Self::MemoryBlocksDestroyed,
+            0x08 => Self::NotEnoughMemory,
+            0x09 => Self::InvalidMemoryBlock,
+            0x0a => Self::InvalidEnvironment,
+            0x0b => Self::InvalidFormat,
+            0x0c => Self::InvalidAccessMode,
+            0x0d => Self::InvalidData,
+            0x0f => Self::InvalidDrive,
+            0x10 => Self::RemoveCurrentDirectory,
+            0x11 => Self::NotSameDevice,
+            0x12 => Self::NoMoreFiles,
+            0x13 => Self::WriteProtected,
+            0x14 => Self::UnknownUnit,
+            0x15 => Self::DriveNotReady,
+            0x16 => Self::UnknownCommand,
+            0x17 => Self::CrcError,
+            0x18 => Self::BadRequestLength,
+            0x19 => Self::SeekError,
+            0x1a => Self::UnknownMedia,
+            0x1b => Self::SectorNotFound,
+            0x1c => Self::OutOfPaper,
+            0x1d => Self::WriteFault,
+            0x1e => Self::ReadFault,
+            0x1f => Self::GeneralFailure,
+            0x20 => Self::SharingViolation,
+            0x21 => Self::LockViolation,
+            0x22 => Self::InvalidDiskChange,
+            0x23 => Self::FcbUnavailable,
+            0x24 => Self::SharingBufferOverflow,
+            0x27 => Self::DiskFull,
+            0x50 => Self::FileExists,
+            0x52 => Self::CannotMakeDirectory,
+            0x53 => Self::CriticalError,
+            code => Self::Other(code),
+        }
+    }
+
+    pub fn code(self) -> u16 {
+        match self {
+            Self::InvalidFunction => 0x01,
+            Self::FileNotFound => 0x02,
+            Self::PathNotFound => 0x03,
+            Self::TooManyOpenFiles => 0x04,
+            Self::AccessDenied => 0x05,
+            Self::InvalidHandle => 0x06,
+            Self::MemoryBlocksDestroyed => 0x07,
+            Self::NotEnoughMemory => 0x08,
+            Self::InvalidMemoryBlock => 0x09,
+            Self::InvalidEnvironment => 0x0a,
+            Self::InvalidFormat => 0x0b,
+            Self::InvalidAccessMode => 0x0c,
+            Self::InvalidData => 0x0d,
+            Self::InvalidDrive => 0x0f,
+            Self::RemoveCurrentDirectory => 0x10,
+            Self::NotSameDevice => 0x11,
+            Self::NoMoreFiles => 0x12,
+            Self::WriteProtected => 0x13,
+            Self::UnknownUnit => 0x14,
+            Self::DriveNotReady => 0x15,
+            Self::UnknownCommand => 0x16,
+            Self::CrcError => 0x17,
+            Self::BadRequestLength => 0x18,
+            Self::SeekError => 0x19,
+            Self::UnknownMedia => 0x1a,
+            Self::SectorNotFound => 0x1b,
+            Self::OutOfPaper => 0x1c,
+            Self::WriteFault => 0x1d,
+            Self::ReadFault => 0x1e,
+            Self::GeneralFailure => 0x1f,
+            Self::SharingViolation => 0x20,
+            Self::LockViolation => 0x21,
+            Self::InvalidDiskChange => 0x22,
+            Self::FcbUnavailable => 0x23,
+            Self::SharingBufferOverflow => 0x24,
+            Self::DiskFull => 0x27,
+            Self::FileExists => 0x50,
+            Self::CannotMakeDirectory => 0x52,
+            Self::CriticalError => 0x53,
+            Self::Other(code) => code,
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::InvalidFunction => "invalid function number",
+            Self::FileNotFound => "file not found",
+            Self::PathNotFound => "path not found",
+            Self::TooManyOpenFiles => "too many open files",
+            Self::AccessDenied => "access denied",
+            Self::InvalidHandle => "invalid handle",
+            Self::MemoryBlocksDestroyed => "memory control blocks destroyed",
+            Self::NotEnoughMemory => "insufficient memory",
+            Self::InvalidMemoryBlock => "invalid memory block address",
+            Self::InvalidEnvironment => "invalid environment",
+            Self::InvalidFormat => "invalid format",
+            Self::InvalidAccessMode => "invalid access mode",
+            Self::InvalidData => "invalid data",
+            Self::InvalidDrive => "invalid drive",
+            Self::RemoveCurrentDirectory => "attempted to remove current directory",
+            Self::NotSameDevice => "not same device",
+            Self::NoMoreFiles => "no more files",
+            Self::WriteProtected => "disk write-protected",
+            Self::UnknownUnit => "unknown unit",
+            Self::DriveNotReady => "drive not ready",
+            Self::UnknownCommand => "unknown command",
+            Self::CrcError => "data error (CRC)",
+            Self::BadRequestLength => "bad request structure length",
+            Self::SeekError => "seek error",
+            Self::UnknownMedia => "unknown media type",
+            Self::SectorNotFound => "sector not found",
+            Self::OutOfPaper => "printer out of paper",
+            Self::WriteFault => "write fault",
+            Self::ReadFault => "read fault",
+            Self::GeneralFailure => "general failure",
+            Self::SharingViolation => "sharing violation",
+            Self::LockViolation => "lock violation",
+            Self::InvalidDiskChange => "invalid disk change",
+            Self::FcbUnavailable => "FCB unavailable",
+            Self::SharingBufferOverflow => "sharing buffer overflow",
+            Self::DiskFull => "disk full",
+            Self::FileExists => "file exists",
+            Self::CannotMakeDirectory => "cannot make directory entry",
+            Self::CriticalError => "fail on INT 24h",
+            Self::Other(_) => "unknown error",
+        }
+    }
+}
+
+impl core::fmt::Display for DosError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Other(code) => write!(f, "DOS error {:#04x}", code),
+            error => f.write_str(error.message()),
+        }
+    }
+}
+
+// what an open handle can do, AL bits 0-2 of INT 21h AH=3Dh
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Access {
+    Read = 0x00,
+    Write = 0x01,
+    ReadWrite = 0x02,
+}
+
+// what other opens of the same file are allowed while we have it, AL bits 4-6
+// Only enforced with SHARE loaded
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Sharing {
+    Compatibility = 0x00,
+    DenyAll = 0x10,
+    DenyWrite = 0x20,
+    DenyRead = 0x30,
+    DenyNone = 0x40,
+}
+
+// Where a seek counts from
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SeekFrom {
+    Start(u32),
+    End(i32),
+    Current(i32),
+}
+
+// A DOS file handle, closed on drop
 pub struct File {
     handle: u32,
-    size: u32
+    size: u32,
+    // kept track of here, so it doesn't take a seek to know it
+    position: u32,
 }
 
 impl File {
-    pub fn open(string: &CStr) -> Option<Self> {
+    // Opens an existing file to read
+    pub fn open(string: &CStr) -> Result<Self, DosError> {
+        Self::open_with(string, Access::Read, Sharing::Compatibility)
+    }
+
+    pub fn open_with(string: &CStr, access: Access, sharing: Sharing) -> Result<Self, DosError> {
         // DOS DPMI function 21h, AH 3Dh - Open File
         // In:
         //     AL = access mode
@@ -112,35 +460,90 @@ impl File {
         //     if failed:
         //     CF set
         //     EAX = DOS error code
+        if lfn_supported() {
+            return Self::from_handle(lfn_open(string, access as u32 | sharing as u32, 0x01)?);
+        }
         let err: u8;
         let eax: u32;
         unsafe { asm!(
             "int 0x21",
             "setc bl",
-            inout("eax") 0x00003D00_u32 => eax,
+            inout("eax") 0x00003D00_u32 | access as u32 | sharing as u32 => eax,
             in("edx") string.as_ptr(),
             inout("bl") 0_u8 => err
         );}
 
-        // Error TODO return error code instead of printing
-        if err == 1 {
-            crate::println!("Could not open file \"{}\" ({}).", string.to_str().unwrap(), eax);
-            return None;
+        if err == 1 { return Err(DosError::from_code(eax as u16)) }
+
+        Self::from_handle(eax)
+    }
+
+    // Makes a new empty file to read and write, an existing one is truncated
+    pub fn create(string: &CStr) -> Result<Self, DosError> {
+        // DOS DPMI function 21h, AH 3Ch - Create or Truncate File
+        // In:
+        //     CX = file attributes
+        //     DS:EDX = pointer to ASCIIZ file name
+        // Out:
+        //     if successful:
+        //     CF clear
+        //     EAX = file handle
+        //
+        //     if failed:
+        //     CF set
+        //     EAX = DOS error code
+        if lfn_supported() {
+            let handle = lfn_open(string, Access::ReadWrite as u32, 0x12)?;
+            return Ok(Self { handle, size: 0, position: 0 });
         }
+        let err: u8;
+        let eax: u32;
+        unsafe { asm!(
+            "int 0x21",
+            "setc bl",
+            inout("eax") 0x00003C00_u32 => eax,
+            in("ecx") 0,
+            in("edx") string.as_ptr(),
+            inout("bl") 0_u8 => err
+        );}
+
+        if err == 1 { return Err(DosError::from_code(eax as u16)) }
+
+        Ok(Self { handle: eax, size: 0, position: 0 })
+    }
 
+    // Opens a file to write at its end, creating it if it isn't there
+    pub fn append(string: &CStr) -> Result<Self, DosError> {
+        let mut file = match Self::open_with(string, Access::Write, Sharing::DenyWrite) {
+            Err(DosError::FileNotFound) => return Self::create(string),
+            file => file?,
+        };
+        file.seek(SeekFrom::End(0))?;
+        Ok(file)
+    }
+
+    fn from_handle(handle: u32) -> Result<Self, DosError> {
         let mut file = Self {
-            handle: eax,
-            size: 0
+            handle,
+            size: 0,
+            position: 0,
         };
         let size = file.find_size()?;
         file.size = size;
 
-        return Some(file);
+        return Ok(file);
     }
 
     pub fn get_size(&self) -> u32 { self.size }
 
-    fn seek(&mut self, origin: u8) -> Option<u32> {
+    // Moves the file position, returns the new one
+    // Going past the end is allowed, writing there fills the gap
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<u32, DosError> {
+        let (origin, offset) = match pos {
+            SeekFrom::Start(offset) => (0, offset),
+            SeekFrom::Current(offset) => (1, offset as u32),
+            SeekFrom::End(offset) => (2, offset as u32),
+        };
         // DOS DPMI function 21h, AH 42h - Set Current File Position
         // In:
         //     AH = 42h
@@ -150,70 +553,985 @@ impl File {
         //         01h = current
         //         02h = end
         //     
-        //     ECX:EDX = file position
+        //     EBX = file handle
+        //     CX:DX = offset, signed for current and end
         // Out:
         //     if successful:
         //     CF clear
-        //     EDX:EAX = current file position
+        //     DX:AX = new file position
         //     
         //     if failed:
         //     CF set
         //     EAX = DOS error code
-        let err: u32;
+        // CX is free once DOS has it, so the carry comes back in CL
+        let ecx: u32;
         let eax: u32;
         let edx: u32;
         unsafe { asm!(
             "int 0x21",
-            "setc bl",
-            inout("eax") 0x00004200_u32 | origin as u32 => eax,
-            in("ecx") 0,
-            inout("edx") 0 => edx,
-            inout("ebx") self.handle => err
+            "setc cl",
+            inout("eax") 0x00004200_u32 | origin => eax,
+            inout("ecx") offset >> 16 => ecx,
+            inout("edx") offset & 0xffff => edx,
+            in("ebx") self.handle,
         );}
 
-        // Error TODO return error code
-        if err == 1 { return None }
+        if ecx as u8 == 1 { return Err(DosError::from_code(eax as u16)) }
 
-        return Some(eax | (edx << 16));
+        self.position = (edx & 0xffff) << 16 | (eax & 0xffff);
+        Ok(self.position)
     }
 
-    fn find_size(&mut self) -> Option<u32> {
-        let size = self.seek(2);
-        self.seek(0)?;
+    pub fn stream_position(&self) -> u32 { self.position }
+
+    // only done at open, so this leaves the position at the start
+    fn find_size(&mut self) -> Result<u32, DosError> {
+        let size = self.seek(SeekFrom::End(0));
+        self.seek(SeekFrom::Start(0))?;
         return size;
     }
 
-    pub fn read(&mut self, buffer: &mut [u8]) -> Option<u32> {
-        // DOS DPMI function 21h, AH 3Fh - Read File
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<u32, DosError> {
+        let eax = read_handle(self.handle, buffer)?;
+
+        self.position += eax;
+        return Ok(eax);
+    }
+
+    // Writes as much of buffer as fits, returns how much that was
+    // Writing 0 bytes truncates (or extends) the file at the current position
+    pub fn write(&mut self, buffer: &[u8]) -> Result<u32, DosError> {
+        let eax = write_handle(self.handle, buffer)?;
+
+        self.position += eax;
+        if buffer.is_empty() {
+            self.size = self.position;
+        } else {
+            self.size = self.size.max(self.position);
+        }
+        Ok(eax)
+    }
+
+    // DOS only writes less than asked for when the disk is full
+    pub fn write_all(&mut self, buffer: &[u8]) -> Result<(), DosError> {
+        if (self.write(buffer)? as usize) < buffer.len() {
+            return Err(DosError::DiskFull);
+        }
+        Ok(())
+    }
+
+    // Cuts the file off at the current position
+    pub fn truncate(&mut self) -> Result<(), DosError> {
+        self.write(&[])?;
+        Ok(())
+    }
+
+    // Makes DOS write its buffers for this file out to disk
+    pub fn commit(&mut self) -> Result<(), DosError> {
+        // DOS DPMI function 21h, AH 68h - Commit File
+        // In:
+        //      EBX = file handle
+        // Out:
+        //      if failed:
+        //      CF set
+        //      AX = DOS error code
+        let err: u8;
+        let eax: u32;
+        unsafe { asm!(
+            "int 0x21",
+            "setc cl",
+            inout("eax") 0x00006800_u32 => eax,
+            in("ebx") self.handle,
+            out("cl") err,
+        );}
+
+        if err == 1 { return Err(DosError::from_code(eax as u16)) }
+        Ok(())
+    }
+
+    // When the file was last written
+    pub fn modified(&self) -> Result<DosDateTime, DosError> {
+        // DOS DPMI function 21h, AX 5700h - Get File Date and Time
         // In:
-        //      AH = 3Fh
         //      EBX = file handle
-        //      ECX = number of bytes to read (size)
-        //      DS:EDX = pointer to buffer to read to (addr)
         // Out:
         //      if successful:
         //      CF clear
-        //      EAX = number of bytes read
-        //      
+        //      CX = time
+        //      DX = date
+        //
+        //      if failed:
+        //      CF set
+        //      AX = DOS error code
+        let err: u32;
+        let eax: u32;
+        let ecx: u32;
+        let edx: u32;
+        unsafe { asm!(
+            "int 0x21",
+            "mov ebx, 0",
+            "setc bl",
+            inout("eax") 0x00005700_u32 => eax,
+            inout("ebx") self.handle => err,
+            out("ecx") ecx,
+            out("edx") edx,
+        );}
+
+        if err == 1 { return Err(DosError::from_code(eax as u16)) }
+        Ok(DosDateTime { date: edx as u16, time: ecx as u16 })
+    }
+
+    // DOS sets the time on close after a write, so this has to come after the last one
+    pub fn set_modified(&mut self, modified: DosDateTime) -> Result<(), DosError> {
+        // DOS DPMI function 21h, AX 5701h - Set File Date and Time
+        // In:
+        //      EBX = file handle
+        //      CX = time
+        //      DX = date
+        // Out:
         //      if failed:
         //      CF set
-        //      EAX = DOS error code
+        //      AX = DOS error code
         let err: u32;
         let eax: u32;
         unsafe { asm!(
             "int 0x21",
             "mov ebx, 0",
             "setc bl",
-            inout("eax") 0x00003F00_u32 => eax,
+            inout("eax") 0x00005701_u32 => eax,
             inout("ebx") self.handle => err,
-            in("ecx") buffer.len(),
-            in("edx") buffer.as_mut_ptr(),
+            in("ecx") modified.time as u32,
+            in("edx") modified.date as u32,
+        );}
+
+        if err == 1 { return Err(DosError::from_code(eax as u16)) }
+        Ok(())
+    }
+
+    fn close_handle(&mut self) -> Result<(), DosError> {
+        // DOS DPMI function 21h, AH 3Eh - Close File
+        // In:
+        //      EBX = file handle
+        // Out:
+        //      if failed:
+        //      CF set
+        //      AX = DOS error code
+        let err: u8;
+        let eax: u32;
+        unsafe { asm!(
+            "int 0x21",
+            "setc cl",
+            inout("eax") 0x00003E00_u32 => eax,
+            in("ebx") self.handle,
+            out("cl") err,
+        );}
+
+        if err == 1 { return Err(DosError::from_code(eax as u16)) }
+        Ok(())
+    }
+
+    // Closes the file and says if that worked, dropping it closes it too but ignores errors
+    pub fn close(mut self) -> Result<(), DosError> {
+        let result = self.close_handle();
+        core::mem::forget(self);
+        result
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let _ = self.close_handle();
+    }
+}
+
+// File attribute bits, as in directory entries and the search attributes of AH=4Eh
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Attributes(pub u8);
+
+impl Attributes {
+    pub const NONE: Self = Self(0);
+    pub const READ_ONLY: Self = Self(0x01);
+    pub const HIDDEN: Self = Self(0x02);
+    pub const SYSTEM: Self = Self(0x04);
+    pub const VOLUME_LABEL: Self = Self(0x08);
+    pub const DIRECTORY: Self = Self(0x10);
+    pub const ARCHIVE: Self = Self(0x20);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for Attributes {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self { Self(self.0 | other.0) }
+}
+
+// A timestamp packed the way DOS stores them, 2 second resolution
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
+pub struct DosDateTime {
+    // bits 9-15 year since 1980, 5-8 month, 0-4 day
+    pub date: u16,
+    // bits 11-15 hours, 5-10 minutes, 0-4 seconds / 2
+    pub time: u16,
+}
+
+impl DosDateTime {
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
+        Self {
+            date: (year.saturating_sub(1980) << 9) | ((month as u16) << 5) | day as u16,
+            time: ((hour as u16) << 11) | ((minute as u16) << 5) | (second as u16 / 2),
+        }
+    }
+
+    pub fn year(self) -> u16 { 1980 + (self.date >> 9) }
+    pub fn month(self) -> u8 { ((self.date >> 5) & 0xf) as u8 }
+    pub fn day(self) -> u8 { (self.date & 0x1f) as u8 }
+    pub fn hour(self) -> u8 { (self.time >> 11) as u8 }
+    pub fn minute(self) -> u8 { ((self.time >> 5) & 0x3f) as u8 }
+    pub fn second(self) -> u8 { ((self.time & 0x1f) * 2) as u8 }
+}
+
+impl core::fmt::Display for DosDateTime {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year(), self.month(), self.day(), self.hour(), self.minute(), self.second())
+    }
+}
+
+// What a directory search finds
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DirEntry {
+    pub name: alloc::string::String,
+    pub size: u32,
+    pub attributes: Attributes,
+    pub modified: DosDateTime,
+}
+
+impl DirEntry {
+    pub fn is_dir(&self) -> bool {
+        self.attributes.contains(Attributes::DIRECTORY)
+    }
+}
+
+// the Disk Transfer Area that AH=4Eh/4Fh fill in
+const DTA_SIZE: usize = 43;
+
+// Switches the DTA to ours, returning the old one as selector:offset
+fn set_dta(dta: *mut u8) -> (u16, u32) {
+    // DOS DPMI function 21h, AH 2Fh - Get Disk Transfer Area Address
+    // Out:
+    //     ES:EBX = address of current DTA
+    let selector: u16;
+    let offset: u32;
+    unsafe { asm!(
+        "push es",
+        "int 0x21",
+        "mov cx, es",
+        "pop es",
+        in("ax") 0x2F00_u16,
+        out("ebx") offset,
+        out("cx") selector,
+    ); }
+    // DOS DPMI function 21h, AH 1Ah - Set Disk Transfer Area Address
+    // In:
+    //     DS:EDX = address of new DTA
+    unsafe { asm!(
+        "int 0x21",
+        in("ax") 0x1A00_u16,
+        in("edx") dta,
+    ); }
+    (selector, offset)
+}
+
+fn restore_dta((selector, offset): (u16, u32)) {
+    unsafe { asm!(
+        "push ds",
+        "mov ds, cx",
+        "int 0x21",
+        "pop ds",
+        in("ax") 0x1A00_u16,
+        in("cx") selector,
+        in("edx") offset,
+    ); }
+}
+
+// Iterates over the files matching a pattern like C:\IMAGES\*.BMP
+// Plain files always match, hidden, system and directory entries
+// only when their attributes are asked for
+pub struct FindFiles {
+    pattern: alloc::ffi::CString,
+    attributes: Attributes,
+    // DOS keeps its search state in here between calls, so it has to stay put
+    dta: alloc::boxed::Box<[u8; DTA_SIZE]>,
+    // long name searches keep theirs behind a handle instead
+    lfn_handle: Option<u16>,
+    started: bool,
+    done: bool,
+}
+
+pub fn find_files(pattern: &CStr, attributes: Attributes) -> FindFiles {
+    FindFiles {
+        pattern: pattern.into(),
+        attributes,
+        dta: alloc::boxed::Box::new([0; DTA_SIZE]),
+        lfn_handle: None,
+        started: false,
+        done: false,
+    }
+}
+
+impl FindFiles {
+    fn find_next(&mut self) -> Result<DirEntry, DosError> {
+        if lfn_supported() { return self.lfn_find_next() }
+        // DOS DPMI function 21h, AH 4Eh - Find First Matching File
+        // In:
+        //     CX = attributes to match
+        //     DS:EDX = pointer to ASCIIZ file name, wildcards allowed
+        // DOS DPMI function 21h, AH 4Fh - Find Next Matching File
+        //     (goes by what's in the DTA)
+        // Out:
+        //     if successful:
+        //     CF clear
+        //     DTA filled in
+        //
+        //     if failed:
+        //     CF set
+        //     EAX = DOS error code
+        let function = if self.started { 0x4F00_u32 } else { 0x4E00_u32 };
+        self.started = true;
+        let old_dta = set_dta(self.dta.as_mut_ptr());
+        let err: u8;
+        let eax: u32;
+        unsafe { asm!(
+            "int 0x21",
+            "setc bl",
+            inout("eax") function => eax,
+            in("ecx") self.attributes.0 as u32,
+            in("edx") self.pattern.as_ptr(),
+            inout("bl") 0_u8 => err
         );}
+        restore_dta(old_dta);
+
+        if err == 1 { return Err(DosError::from_code(eax as u16)) }
+        Ok(self.entry())
+    }
+
+    fn lfn_find_next(&mut self) -> Result<DirEntry, DosError> {
+        // DOS function 21h, AX 714Eh - LFN Find First Matching File
+        // In:
+        //     CL = allowable attributes
+        //     CH = required attributes
+        //     SI = 1 for DOS style times
+        //     DS:DX = pointer to ASCIIZ file name, wildcards allowed
+        //     ES:DI = pointer to find data record
+        // Out:
+        //     if successful:
+        //     CF clear
+        //     AX = search handle
+        //
+        //     if failed:
+        //     CF set
+        //     AX = DOS error code
+        // DOS function 21h, AX 714Fh - LFN Find Next Matching File
+        // In:
+        //     BX = search handle
+        //     SI = 1 for DOS style times
+        //     ES:DI = pointer to find data record
+        let mut regs = DpmiRegs::zero();
+        regs.ecx = self.attributes.0 as u32;
+        regs.esi = 1;
+        regs.edx = TRANSFER_PATH as u32;
+        regs.edi = TRANSFER_DATA as u32;
+        match self.lfn_handle {
+            Some(handle) => {
+                regs.eax = 0x714F;
+                regs.ebx = handle as u32;
+            }
+            None => regs.eax = 0x714E,
+        }
+        let record = lfn_call(&mut regs, &[&self.pattern], |buffer| {
+            let mut record = [0; FIND_DATA_SIZE];
+            record.copy_from_slice(&buffer[TRANSFER_DATA as usize..][..FIND_DATA_SIZE]);
+            record
+        })?;
+        if self.lfn_handle.is_none() {
+            self.lfn_handle = Some(regs.eax as u16);
+        }
+
+        let word = |offset: usize| u16::from_le_bytes([record[offset], record[offset + 1]]);
+        let name = &record[0x2C..0x130];
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+        Ok(DirEntry {
+            name: crate::codepage::decode(name),
+            // files over 4GB don't happen with FAT
+            size: u32::from_le_bytes([record[0x20], record[0x21], record[0x22], record[0x23]]),
+            attributes: Attributes(record[0]),
+            // with DOS style times the last write time starts with the time, then the date
+            modified: DosDateTime { time: word(0x14), date: word(0x16) },
+        })
+    }
+
+    fn entry(&self) -> DirEntry {
+        let dta = &self.dta;
+        let name = &dta[0x1E..0x2B];
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+        DirEntry {
+            name: crate::codepage::decode(name),
+            size: u32::from_le_bytes([dta[0x1A], dta[0x1B], dta[0x1C], dta[0x1D]]),
+            attributes: Attributes(dta[0x15]),
+            modified: DosDateTime {
+                time: u16::from_le_bytes([dta[0x16], dta[0x17]]),
+                date: u16::from_le_bytes([dta[0x18], dta[0x19]]),
+            },
+        }
+    }
+}
+
+impl Iterator for FindFiles {
+    type Item = Result<DirEntry, DosError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done { return None }
+        match self.find_next() {
+            Ok(entry) => Some(Ok(entry)),
+            // running out is how every search ends, not an error
+            Err(DosError::NoMoreFiles | DosError::FileNotFound) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl Drop for FindFiles {
+    fn drop(&mut self) {
+        // DOS function 21h, AX 71A1h - LFN Find Close
+        // In:
+        //     BX = search handle
+        if let Some(handle) = self.lfn_handle {
+            let mut regs = DpmiRegs::zero();
+            regs.eax = 0x71A1;
+            regs.ebx = handle as u32;
+            real_int(0x21, &mut regs);
+        }
+    }
+}
+
+// Long file names, INT 21h AX=71xxh
+// Windows 9x DOS boxes and DOSBox-X with LFN on have them, and DOS/32A doesn't
+// translate them, so they go through real mode with the names in conventional memory
+
+// longest path the LFN functions take, with the 0
+const LFN_PATH_MAX: usize = 260;
+// the size of the LFN find data record
+const FIND_DATA_SIZE: usize = 318;
+// where things go in the transfer buffer
+const TRANSFER_PATH: u16 = 0;
+const TRANSFER_PATH2: u16 = 0x110;
+const TRANSFER_DATA: u16 = 0x220;
+const TRANSFER_SIZE: usize = 0x400;
+
+static TRANSFER_BUFFER: spin::Mutex<Option<DosMemory>> = spin::Mutex::new(None);
+// 0 until we've checked, then 1 if there are long names and 2 if not
+static LFN_SUPPORT: core::sync::atomic::AtomicU8 = core::sync::atomic::AtomicU8::new(0);
+
+// Runs f with the transfer buffer, allocating it the first time
+fn with_transfer_buffer<R>(f: impl FnOnce(&mut DosMemory) -> R) -> Result<R, DosError> {
+    let mut buffer = TRANSFER_BUFFER.lock();
+    if buffer.is_none() {
+        *buffer = Some(DosMemory::alloc(TRANSFER_SIZE).ok_or(DosError::NotEnoughMemory)?);
+    }
+    Ok(f(buffer.as_mut().unwrap()))
+}
+
+// Whether the 71xxh functions are there, checked once
+pub fn lfn_supported() -> bool {
+    use core::sync::atomic::Ordering;
+    match LFN_SUPPORT.load(Ordering::Relaxed) {
+        1 => true,
+        2 => false,
+        _ => {
+            // DOS without them fails any 71xxh call with AX=7100h,
+            // some versions forget to set the carry
+            let mut regs = DpmiRegs::zero();
+            regs.eax = 0x7147;
+            regs.esi = TRANSFER_DATA as u32;
+            let supported = lfn_call(&mut regs, &[], |_| ()).is_ok() && regs.eax as u16 != 0x7100;
+            LFN_SUPPORT.store(if supported { 1 } else { 2 }, Ordering::Relaxed);
+            supported
+        }
+    }
+}
+
+// Runs an LFN function in real mode with DS and ES on the transfer buffer,
+// the paths copied to TRANSFER_PATH and TRANSFER_PATH2
+// The other registers are up to the caller, and out gets a look at the buffer afterwards
+fn lfn_call<R>(regs: &mut DpmiRegs, paths: &[&CStr], out: impl FnOnce(&[u8]) -> R) -> Result<R, DosError> {
+    with_transfer_buffer(|buffer| {
+        for (path, offset) in paths.iter().zip([TRANSFER_PATH, TRANSFER_PATH2]) {
+            let path = path.to_bytes_with_nul();
+            if path.len() > LFN_PATH_MAX { return Err(DosError::PathNotFound) }
+            buffer.as_mut_slice()[offset as usize..][..path.len()].copy_from_slice(path);
+        }
+        regs.ds = buffer.segment;
+        regs.es = buffer.segment;
+        real_int(0x21, regs);
+        if regs.status_flags & 1 != 0 { return Err(DosError::from_code(regs.eax as u16)) }
+        Ok(out(buffer.as_mut_slice()))
+    })?
+}
+
+// For the functions that take one path in DS:DX and nothing else
+fn lfn_path_call(function: u32, path: &CStr) -> Result<(), DosError> {
+    let mut regs = DpmiRegs::zero();
+    regs.eax = function;
+    regs.edx = TRANSFER_PATH as u32;
+    lfn_call(&mut regs, &[path], |_| ())
+}
+
+// DOS function 21h, AX 716Ch - LFN Extended Open/Create
+// In:
+//     BX = access and sharing mode
+//     CX = attributes for a new file
+//     DX = action:
+//
+//         01h = open if it's there
+//         02h = truncate if it's there
+//         10h = create if it isn't
+//
+//     DS:SI = pointer to ASCIIZ file name
+// Out:
+//     if successful:
+//     CF clear
+//     AX = file handle
+//
+//     if failed:
+//     CF set
+//     AX = DOS error code
+fn lfn_open(path: &CStr, mode: u32, action: u32) -> Result<u32, DosError> {
+    let mut regs = DpmiRegs::zero();
+    regs.eax = 0x716C;
+    regs.ebx = mode;
+    regs.edx = action;
+    regs.esi = TRANSFER_PATH as u32;
+    lfn_call(&mut regs, &[path], |_| ())?;
+    Ok(regs.eax & 0xffff)
+}
+
+// Runs one of the functions that only take a path in DS:EDX, returns EAX
+fn path_call(function: u32, path: &CStr) -> Result<u32, DosError> {
+    let err: u8;
+    let eax: u32;
+    unsafe { asm!(
+        "int 0x21",
+        "setc bl",
+        inout("eax") function => eax,
+        in("edx") path.as_ptr(),
+        inout("bl") 0_u8 => err
+    );}
+
+    if err == 1 { return Err(DosError::from_code(eax as u16)) }
+    Ok(eax)
+}
+
+pub fn create_dir(path: &CStr) -> Result<(), DosError> {
+    // DOS DPMI function 21h, AH 39h - Create Subdirectory
+    // In:
+    //     DS:EDX = pointer to ASCIIZ path name
+    // Out:
+    //     if failed:
+    //     CF set
+    //     AX = DOS error code
+    if lfn_supported() { return lfn_path_call(0x7139, path) }
+    path_call(0x3900, path).map(|_| ())
+}
+
+// Only empty directories can go, and not the current one
+pub fn remove_dir(path: &CStr) -> Result<(), DosError> {
+    // DOS DPMI function 21h, AH 3Ah - Remove Subdirectory
+    // In:
+    //     DS:EDX = pointer to ASCIIZ path name
+    // Out:
+    //     if failed:
+    //     CF set
+    //     AX = DOS error code
+    if lfn_supported() { return lfn_path_call(0x713A, path) }
+    path_call(0x3A00, path).map(|_| ())
+}
+
+// Each drive has its own current directory, this sets the one of the drive in the path
+pub fn set_current_dir(path: &CStr) -> Result<(), DosError> {
+    // DOS DPMI function 21h, AH 3Bh - Set Current Directory
+    // In:
+    //     DS:EDX = pointer to ASCIIZ path name
+    // Out:
+    //     if failed:
+    //     CF set
+    //     AX = DOS error code
+    if lfn_supported() { return lfn_path_call(0x713B, path) }
+    path_call(0x3B00, path).map(|_| ())
+}
+
+pub fn remove_file(path: &CStr) -> Result<(), DosError> {
+    // DOS DPMI function 21h, AH 41h - Delete File
+    // In:
+    //     DS:EDX = pointer to ASCIIZ file name
+    // Out:
+    //     if failed:
+    //     CF set
+    //     AX = DOS error code
+    // the LFN one takes wildcards when SI is 1, we leave it at 0
+    if lfn_supported() { return lfn_path_call(0x7141, path) }
+    path_call(0x4100, path).map(|_| ())
+}
+
+pub fn file_attributes(path: &CStr) -> Result<Attributes, DosError> {
+    // DOS DPMI function 21h, AX 4300h - Get File Attributes
+    // In:
+    //     DS:EDX = pointer to ASCIIZ file name
+    // Out:
+    //     if successful:
+    //     CF clear
+    //     CX = attributes
+    //
+    //     if failed:
+    //     CF set
+    //     AX = DOS error code
+    // The LFN one is AX=7143h with BL=0, and the same otherwise
+    if lfn_supported() {
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x7143;
+        regs.edx = TRANSFER_PATH as u32;
+        lfn_call(&mut regs, &[path], |_| ())?;
+        return Ok(Attributes(regs.ecx as u8));
+    }
+    let err: u8;
+    let eax: u32;
+    let ecx: u32;
+    unsafe { asm!(
+        "int 0x21",
+        "setc bl",
+        inout("eax") 0x00004300_u32 => eax,
+        out("ecx") ecx,
+        in("edx") path.as_ptr(),
+        inout("bl") 0_u8 => err
+    );}
+
+    if err == 1 { return Err(DosError::from_code(eax as u16)) }
+    Ok(Attributes(ecx as u8))
+}
+
+// The directory and volume label bits can't be set this way
+pub fn set_file_attributes(path: &CStr, attributes: Attributes) -> Result<(), DosError> {
+    // DOS DPMI function 21h, AX 4301h - Set File Attributes
+    // In:
+    //     CX = attributes
+    //     DS:EDX = pointer to ASCIIZ file name
+    // Out:
+    //     if failed:
+    //     CF set
+    //     AX = DOS error code
+    // The LFN one is AX=7143h with BL=1, and the same otherwise
+    if lfn_supported() {
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x7143;
+        regs.ebx = 1;
+        regs.ecx = attributes.0 as u32;
+        regs.edx = TRANSFER_PATH as u32;
+        return lfn_call(&mut regs, &[path], |_| ());
+    }
+    let err: u8;
+    let eax: u32;
+    unsafe { asm!(
+        "int 0x21",
+        "setc bl",
+        inout("eax") 0x00004301_u32 => eax,
+        in("ecx") attributes.0 as u32,
+        in("edx") path.as_ptr(),
+        inout("bl") 0_u8 => err
+    );}
 
-        // Error TODO return error code
+    if err == 1 { return Err(DosError::from_code(eax as u16)) }
+    Ok(())
+}
+
+// Renames or moves a file, both paths have to be on the same drive
+pub fn rename(from: &CStr, to: &CStr) -> Result<(), DosError> {
+    // DOS DPMI function 21h, AH 56h - Rename File
+    // In:
+    //     DS:EDX = pointer to ASCIIZ old name
+    //     ES:EDI = pointer to ASCIIZ new name
+    // Out:
+    //     if failed:
+    //     CF set
+    //     AX = DOS error code
+    // The LFN one is AX=7156h and the same otherwise
+    if lfn_supported() {
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x7156;
+        regs.edx = TRANSFER_PATH as u32;
+        regs.edi = TRANSFER_PATH2 as u32;
+        return lfn_call(&mut regs, &[from, to], |_| ());
+    }
+    // ES is the same flat segment as DS, so EDI can just point at it
+    let err: u8;
+    let eax: u32;
+    unsafe { asm!(
+        "int 0x21",
+        "setc bl",
+        inout("eax") 0x00005600_u32 => eax,
+        in("edx") from.as_ptr(),
+        in("edi") to.as_ptr(),
+        inout("bl") 0_u8 => err
+    );}
+
+    if err == 1 { return Err(DosError::from_code(eax as u16)) }
+    Ok(())
+}
+
+// Drives are numbered from 0 for A:
+pub fn current_drive() -> u8 {
+    // DOS DPMI function 21h, AH 19h - Get Current Drive
+    // Out:
+    //     AL = drive, 0 = A:
+    let eax: u32;
+    unsafe { asm!(
+        "int 0x21",
+        inout("eax") 0x00001900_u32 => eax,
+    );}
+    eax as u8
+}
+
+// Returns how many drive letters there are
+pub fn set_current_drive(drive: u8) -> Result<u8, DosError> {
+    // DOS DPMI function 21h, AH 0Eh - Select Default Drive
+    // In:
+    //     DL = drive, 0 = A:
+    // Out:
+    //     AL = number of logical drives
+    let eax: u32;
+    unsafe { asm!(
+        "int 0x21",
+        inout("eax") 0x00000E00_u32 => eax,
+        in("edx") drive as u32,
+    );}
+    // there's no error, the drive just doesn't change
+    if current_drive() != drive { return Err(DosError::InvalidDrive) }
+    Ok(eax as u8)
+}
+
+// The current directory of a drive as C:\PATH, drive None for the current one
+pub fn current_dir(drive: Option<u8>) -> Result<alloc::string::String, DosError> {
+    let drive = drive.unwrap_or_else(current_drive);
+    // DOS DPMI function 21h, AH 47h - Get Current Directory
+    // In:
+    //     DL = drive, 0 = default, 1 = A:
+    //     DS:ESI = pointer to 64 byte buffer
+    // Out:
+    //     if successful:
+    //     CF clear
+    //     buffer has the ASCIIZ path without drive or leading backslash
+    //
+    //     if failed:
+    //     CF set
+    //     AX = DOS error code
+    // The LFN one is AX=7147h with room for a 260 byte path, and the same otherwise
+    if lfn_supported() {
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x7147;
+        regs.edx = drive as u32 + 1;
+        regs.esi = TRANSFER_DATA as u32;
+        let path = lfn_call(&mut regs, &[], |buffer| {
+            let path = &buffer[TRANSFER_DATA as usize..][..LFN_PATH_MAX];
+            let len = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+            crate::codepage::decode(&path[..len])
+        })?;
+        return Ok(alloc::format!("{}:\\{}", (b'A' + drive) as char, path));
+    }
+    let mut buffer = [0_u8; 64];
+    let err: u8;
+    let eax: u32;
+    // LLVM keeps ESI for itself, so it gets saved around the call
+    unsafe { asm!(
+        "push esi",
+        "mov esi, {buffer}",
+        "int 0x21",
+        "pop esi",
+        "setc bl",
+        buffer = in(reg) buffer.as_mut_ptr(),
+        inout("eax") 0x00004700_u32 => eax,
+        in("edx") drive as u32 + 1,
+        inout("bl") 0_u8 => err
+    );}
+
+    if err == 1 { return Err(DosError::from_code(eax as u16)) }
+    let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    Ok(alloc::format!("{}:\\{}", (b'A' + drive) as char, crate::codepage::decode(&buffer[..len])))
+}
+
+// the handles every program starts with
+pub const STDIN: u32 = 0;
+pub const STDOUT: u32 = 1;
+pub const STDERR: u32 = 2;
+
+// Reads from any handle, files or devices, returns how much got read
+// The console gives back a line at a time, with DOS doing the editing
+pub fn read_handle(handle: u32, buffer: &mut [u8]) -> Result<u32, DosError> {
+    // DOS DPMI function 21h, AH 3Fh - Read File
+    // In:
+    //      AH = 3Fh
+    //      EBX = file handle
+    //      ECX = number of bytes to read (size)
+    //      DS:EDX = pointer to buffer to read to (addr)
+    // Out:
+    //      if successful:
+    //      CF clear
+    //      EAX = number of bytes read
+    //      
+    //      if failed:
+    //      CF set
+    //      EAX = DOS error code
+    let err: u32;
+    let eax: u32;
+    unsafe { asm!(
+        "int 0x21",
+        "mov ebx, 0",
+        "setc bl",
+        inout("eax") 0x00003F00_u32 => eax,
+        inout("ebx") handle => err,
+        in("ecx") buffer.len(),
+        in("edx") buffer.as_mut_ptr(),
+    );}
+
+    if err == 1 { return Err(DosError::from_code(eax as u16)) }
+    Ok(eax)
+}
+
+// Whether a handle is the keyboard rather than a file or a pipe
+pub fn is_console_input(handle: u32) -> bool {
+    // DOS DPMI function 21h, AX 4400h - Get Device Information
+    // In:
+    //      EBX = handle
+    // Out:
+    //      if successful:
+    //      CF clear
+    //      DX = device information, bit 7 set for devices,
+    //      and then bit 0 set for the console input
+    //
+    //      if failed:
+    //      CF set
+    //      EAX = DOS error code
+    let err: u32;
+    let edx: u32;
+    unsafe { asm!(
+        "int 0x21",
+        "mov ebx, 0",
+        "setc bl",
+        inout("eax") 0x00004400_u32 => _,
+        inout("ebx") handle => err,
+        out("edx") edx,
+    );}
+    err == 0 && edx & 0x81 == 0x81
+}
+
+// Writes to any handle, files or devices, returns how much got written
+pub fn write_handle(handle: u32, buffer: &[u8]) -> Result<u32, DosError> {
+    // DOS DPMI function 21h, AH 40h - Write File
+    // In:
+    //      AH = 40h
+    //      EBX = file handle
+    //      ECX = number of bytes to write (size)
+    //      DS:EDX = pointer to buffer to write from (addr)
+    // Out:
+    //      if successful:
+    //      CF clear
+    //      EAX = number of bytes written
+    //
+    //      if failed:
+    //      CF set
+    //      EAX = DOS error code
+    let err: u32;
+    let eax: u32;
+    unsafe { asm!(
+        "int 0x21",
+        "mov ebx, 0",
+        "setc bl",
+        inout("eax") 0x00004000_u32 => eax,
+        inout("ebx") handle => err,
+        in("ecx") buffer.len(),
+        in("edx") buffer.as_ptr(),
+    );}
+
+    if err == 1 { return Err(DosError::from_code(eax as u16)) }
+    Ok(eax)
+}
+
+// Converts a real mode segment:offset pointer into one we can use
+// DOS/32A gives us a zero based flat data segment, so the first megabyte
+// is just at its linear address (same as the 0xA0000 VGA buffer)
+pub fn real_ptr(segment: u16, offset: u16) -> *const u8 {
+    (((segment as u32) << 4) + offset as u32) as *const u8
+}
+
+// A block of conventional memory, for handing data to real mode code
+pub struct DosMemory {
+    pub segment: u16,
+    selector: u16,
+    size: usize,
+}
+
+impl DosMemory {
+    pub fn alloc(size: usize) -> Option<Self> {
+        // DPMI function 0100h - Allocate DOS Memory Block
+        // In:
+        //     BX = number of paragraphs
+        // Out:
+        //     if successful:
+        //     CF clear
+        //     AX = real mode segment of the block
+        //     DX = selector for the block
+        //
+        //     if failed:
+        //     CF set
+        //     AX = DOS error code
+        //     BX = size of largest available block in paragraphs
+        // a block can't be 1MB or more, that doesn't fit in BX
+        let paragraphs = u16::try_from(size.div_ceil(16)).ok()?;
+        let err: u8;
+        let segment: u16;
+        let selector: u16;
+        unsafe { asm!(
+            "int 0x31",
+            "setc cl",
+            inout("ax") 0x0100_u16 => segment,
+            inout("bx") paragraphs => _,
+            out("dx") selector,
+            out("cl") err,
+        );}
         if err == 1 { return None }
+        Some(Self { segment, selector, size })
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(real_ptr(self.segment, 0) as *mut u8, self.size) }
+    }
+}
 
-        return Some(eax);
+impl Drop for DosMemory {
+    fn drop(&mut self) {
+        // DPMI function 0101h - Free DOS Memory Block
+        // In:
+        //     DX = selector of the block
+        unsafe { asm!(
+            "int 0x31",
+            inout("ax") 0x0101_u16 => _,
+            in("dx") self.selector,
+        );}
     }
 }
 
@@ -275,18 +1593,8 @@ impl IntHandler {
     }
 }
 
-pub fn dpmi_print(string: &str) {
-    // DOS DPMI function 21h, AH 9h
-    // Prints string pointed to
-    // by EDX, terminated with $
-    unsafe { asm!(
-        "int 0x21",
-        in("ah") 0x9_u8,
-        in("edx") string.as_ptr()
-    ); }
-}
-
 pub fn dpmi_exit() -> ! {
+    flush_stdout();
     // DOS DPMI function Exit: 21h, AH 4Ch
     unsafe { asm!(
         "int 0x21",
@@ -296,24 +1604,93 @@ pub fn dpmi_exit() -> ! {
 }
 
 #[macro_export]
-#[doc(hidden)]
-macro_rules! _raw_print {
+macro_rules! print {
     ($($arg:tt)*) => ($crate::dpmi::_print(format_args!($($arg)*)));
 }
 #[macro_export]
-macro_rules! print {
-    ($($arg:tt)*) => ($crate::_raw_print!("{}$", format_args!($($arg)*)));
+macro_rules! println {
+    () => ($crate::print!("\r\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\r\n", format_args!($($arg)*)));
 }
 #[macro_export]
-macro_rules! println {
-    () => ($crate::_raw_print!("\r\n$"));
-    ($($arg:tt)*) => ($crate::_raw_print!("{}\r\n$", format_args!($($arg)*)));
+macro_rules! eprint {
+    ($($arg:tt)*) => ($crate::dpmi::_eprint(format_args!($($arg)*)));
+}
+#[macro_export]
+macro_rules! eprintln {
+    () => ($crate::eprint!("\r\n"));
+    ($($arg:tt)*) => ($crate::eprint!("{}\r\n", format_args!($($arg)*)));
+}
+
+// stdout gets written a line at a time, or when this fills up
+const STDOUT_BUFFER_SIZE: usize = 512;
+static STDOUT_BUFFER: spin::Mutex<heapless::Vec<u8, STDOUT_BUFFER_SIZE>> = spin::Mutex::new(heapless::Vec::new());
+
+// Encodes text to the DOS code page as it's formatted
+struct HandleWriter<F: FnMut(&[u8])> {
+    out: F,
+}
+
+impl<F: FnMut(&[u8])> Write for HandleWriter<F> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let page = crate::codepage::active();
+        let mut chunk = [0_u8; 64];
+        let mut len = 0;
+        for c in s.chars() {
+            chunk[len] = page.encode_char(c);
+            len += 1;
+            if len == chunk.len() {
+                (self.out)(&chunk);
+                len = 0;
+            }
+        }
+        // writing nothing to a file handle truncates the file there
+        if len > 0 {
+            (self.out)(&chunk[..len]);
+        }
+        Ok(())
+    }
+}
+
+// Writes out whatever print! has buffered, for before reading input or exiting
+// If a print! is in the middle of it, like when it panicked, this leaves it alone
+pub fn flush_stdout() {
+    if let Some(mut buffer) = STDOUT_BUFFER.try_lock() {
+        // an empty write would truncate stdout if it's redirected to a file
+        if buffer.is_empty() { return }
+        // nothing to do about it if stdout is gone
+        let _ = write_handle(STDOUT, &buffer);
+        buffer.clear();
+    }
 }
 
 #[doc(hidden)]
 pub fn _print(args: Arguments) {
-    let mut s: alloc::string::String = alloc::string::String::new();
-    s.write_fmt(args).unwrap();
-    dpmi_print(s.as_str());
+    // goes to the text console instead, if one was set up
+    if crate::console::_print(args) { return }
+    let mut buffer = STDOUT_BUFFER.lock();
+    let mut newline = false;
+    let _ = HandleWriter { out: |bytes: &[u8]| {
+        newline |= bytes.contains(&b'\n');
+        if buffer.len() + bytes.len() > STDOUT_BUFFER_SIZE {
+            let _ = write_handle(STDOUT, &buffer);
+            buffer.clear();
+        }
+        if buffer.extend_from_slice(bytes).is_err() {
+            let _ = write_handle(STDOUT, bytes);
+        }
+    } }.write_fmt(args);
+    if newline {
+        let _ = write_handle(STDOUT, &buffer);
+        buffer.clear();
+    }
 }
 
+#[doc(hidden)]
+pub fn _eprint(args: Arguments) {
+    // the console is the screen, so errors go there too
+    if crate::console::_print(args) { return }
+    // stdout first, so the two come out in order
+    flush_stdout();
+    let _ = HandleWriter { out: |bytes: &[u8]| { let _ = write_handle(STDERR, bytes); } }.write_fmt(args);
+}
@@ -91,12 +91,13 @@ static ALLOCATOR: DpmiAlloc = DpmiAlloc::new();
 pub fn _print(args: core::fmt::Arguments) {
     let mut s: String<256> = String::new();
     s.write_fmt(args).unwrap();
-    crate::dpmi::dpmi_print(s.as_str());
+    // straight to stderr, print! would need to allocate
+    let _ = crate::dpmi::write_handle(crate::dpmi::STDERR, s.as_bytes());
 }
 
 #[alloc_error_handler]
 fn alloc_error_handler(layout: Layout) -> ! {
-    _print(format_args!("allocation error: {:?}$", layout));
+    _print(format_args!("allocation error: {:?}\r\n", layout));
     crate::dpmi::dpmi_exit();
 }
 
@@ -0,0 +1,69 @@
+#![allow(dead_code)]
+use alloc::{string::String, vec::Vec};
+
+use crate::codepage;
+use crate::dpmi;
+
+// The variables DOS gave us when we started, SET after that doesn't reach us
+pub struct Environment {
+    vars: Vec<(String, String)>,
+    program_path: Option<String>,
+}
+
+impl Environment {
+    pub fn read() -> Self {
+        Self::parse(&dpmi::environment_block())
+    }
+
+    // NAME=value strings each ending in a 0, an empty one ends the list
+    // After that DOS 3+ puts a word count of strings (always 1) and our full path
+    pub fn parse(block: &[u8]) -> Self {
+        let mut vars = Vec::new();
+        let mut rest = block;
+        loop {
+            let end = match rest.iter().position(|&b| b == 0) {
+                Some(end) => end,
+                None => return Self { vars, program_path: None },
+            };
+            if end == 0 { break }
+            let var = codepage::decode(&rest[..end]);
+            // COMSPEC and friends always have an =, but be safe about it
+            match var.split_once('=') {
+                Some((name, value)) => vars.push((name.into(), value.into())),
+                None => vars.push((var, String::new())),
+            }
+            rest = &rest[end + 1..];
+        }
+
+        let program_path = rest.get(1..3)
+            .filter(|count| u16::from_le_bytes([count[0], count[1]]) >= 1)
+            .and_then(|_| {
+                let path = &rest[3..];
+                let end = path.iter().position(|&b| b == 0)?;
+                (end > 0).then(|| codepage::decode(&path[..end]))
+            });
+        Self { vars, program_path }
+    }
+
+    // Names don't care about case, SET makes them uppercase anyway
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    // Where we were run from, like C:\TOOLS\VIEWER.EXE
+    pub fn program_path(&self) -> Option<&str> {
+        self.program_path.as_deref()
+    }
+}
+
+pub fn var(name: &str) -> Option<String> {
+    Environment::read().get(name).map(String::from)
+}
+
+pub fn program_path() -> Option<String> {
+    Environment::read().program_path
+}
@@ -0,0 +1,223 @@
+#![allow(dead_code)]
+use alloc::vec::Vec;
+
+use crate::codepage::CodePage;
+use crate::dpmi::{self, DpmiRegs};
+use crate::vga::Framebuffer;
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE512: u8 = 0x01;
+const PSF1_MODEHASTAB: u8 = 0x02;
+const PSF1_SEPARATOR: u16 = 0xFFFF;
+const PSF1_STARTSEQ: u16 = 0xFFFE;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xFF;
+const PSF2_STARTSEQ: u8 = 0xFE;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum RomFont {
+    Font8x8,
+    Font8x14,
+    Font8x16,
+}
+
+pub struct Font {
+    pub width: usize,
+    pub height: usize,
+    glyph_count: usize,
+    bytes_per_row: usize,
+    glyphs: Vec<u8>,
+    // (character, glyph index), sorted so we can binary search it
+    unicode: Vec<(char, u16)>,
+}
+
+// BIOS function INT 10,1130 Get font information
+// In:
+//     BH = pointer specifier
+//         02h = 8x14
+//         03h = 8x8, characters 00h-7Fh
+//         04h = 8x8, characters 80h-FFh (INT 1F)
+//         06h = 8x16 (VGA only)
+// Out:
+//     ES:BP = real mode pointer to font table
+//     CX = bytes per character
+//     DL = character rows on screen - 1
+fn rom_font_ptr(specifier: u8) -> *const u8 {
+    let mut regs = DpmiRegs::zero();
+    regs.eax = 0x1130;
+    regs.ebx = (specifier as u32) << 8;
+    dpmi::real_int(0x10, &mut regs);
+    dpmi::real_ptr(regs.es, regs.ebp as u16)
+}
+
+fn rom_font_copy(specifier: u8, count: usize, height: usize, glyphs: &mut Vec<u8>) {
+    let ptr = rom_font_ptr(specifier);
+    let table = unsafe { core::slice::from_raw_parts(ptr, count * height) };
+    glyphs.extend_from_slice(table);
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+impl Font {
+    // Copies one of the fonts out of the VGA BIOS
+    pub fn rom(font: RomFont) -> Self {
+        let mut glyphs = Vec::new();
+        let height = match font {
+            RomFont::Font8x8 => {
+                // the 8x8 font is split in two halves
+                rom_font_copy(0x03, 128, 8, &mut glyphs);
+                rom_font_copy(0x04, 128, 8, &mut glyphs);
+                8
+            }
+            RomFont::Font8x14 => {
+                rom_font_copy(0x02, 256, 14, &mut glyphs);
+                14
+            }
+            RomFont::Font8x16 => {
+                rom_font_copy(0x06, 256, 16, &mut glyphs);
+                16
+            }
+        };
+        Self { width: 8, height, glyph_count: 256, bytes_per_row: 1, glyphs, unicode: Vec::new() }
+    }
+
+    // Loads a PC Screen Font, version 1 or 2
+    pub fn from_psf(source: &[u8]) -> Option<Self> {
+        if source.len() >= 4 && source[..4] == PSF2_MAGIC {
+            Self::from_psf2(source)
+        } else if source.len() >= 2 && source[..2] == PSF1_MAGIC {
+            Self::from_psf1(source)
+        } else {
+            None
+        }
+    }
+
+    fn from_psf1(source: &[u8]) -> Option<Self> {
+        if source.len() < 4 { return None }
+        let mode = source[2];
+        let height = source[3] as usize;
+        let glyph_count = if mode & PSF1_MODE512 != 0 { 512 } else { 256 };
+
+        let glyphs_end = 4 + glyph_count * height;
+        if height == 0 || source.len() < glyphs_end { return None }
+
+        let mut unicode = Vec::new();
+        if mode & PSF1_MODEHASTAB != 0 {
+            // each glyph gets a list of u16 codepoints ended by FFFF,
+            // multi-character sequences start with FFFE and are skipped
+            let mut glyph = 0_u16;
+            let mut in_seq = false;
+            for pair in source[glyphs_end..].chunks_exact(2) {
+                match u16::from_le_bytes([pair[0], pair[1]]) {
+                    PSF1_SEPARATOR => { glyph += 1; in_seq = false; }
+                    PSF1_STARTSEQ => { in_seq = true; }
+                    _ if in_seq => {}
+                    c => if let Some(c) = char::from_u32(c as u32) {
+                        unicode.push((c, glyph));
+                    }
+                }
+            }
+        }
+
+        Some(Self::with_unicode(8, height, glyph_count, source[4..glyphs_end].into(), unicode))
+    }
+
+    fn from_psf2(source: &[u8]) -> Option<Self> {
+        if source.len() < 32 { return None }
+        let header_size = read_u32(source, 8) as usize;
+        let flags = read_u32(source, 12);
+        let glyph_count = read_u32(source, 16) as usize;
+        let glyph_size = read_u32(source, 20) as usize;
+        let height = read_u32(source, 24) as usize;
+        let width = read_u32(source, 28) as usize;
+
+        let bytes_per_row = width.div_ceil(8);
+        if width == 0 || height == 0 || glyph_size != bytes_per_row * height {
+            return None
+        }
+        let glyphs_end = glyph_count.checked_mul(glyph_size)?.checked_add(header_size)?;
+        if source.len() < glyphs_end { return None }
+
+        let mut unicode = Vec::new();
+        if flags & PSF2_HAS_UNICODE_TABLE != 0 {
+            // same idea as PSF1, but the codepoints are UTF-8 and the markers are bytes
+            for (glyph, entry) in source[glyphs_end..].split(|&b| b == PSF2_SEPARATOR).enumerate() {
+                let singles = entry.split(|&b| b == PSF2_STARTSEQ).next().unwrap_or(&[]);
+                if let Ok(s) = core::str::from_utf8(singles) {
+                    unicode.extend(s.chars().map(|c| (c, glyph as u16)));
+                }
+            }
+        }
+
+        let glyphs = source[header_size..glyphs_end].into();
+        Some(Self::with_unicode(width, height, glyph_count, glyphs, unicode))
+    }
+
+    fn with_unicode(width: usize, height: usize, glyph_count: usize, glyphs: Vec<u8>, mut unicode: Vec<(char, u16)>) -> Self {
+        unicode.sort_unstable_by_key(|&(c, _)| c);
+        unicode.dedup_by_key(|&mut (c, _)| c);
+        Self { width, height, glyph_count, bytes_per_row: width.div_ceil(8), glyphs, unicode }
+    }
+
+    fn glyph_index(&self, c: char) -> usize {
+        if !self.unicode.is_empty() {
+            return match self.unicode.binary_search_by_key(&c, |&(c, _)| c) {
+                Ok(i) if (self.unicode[i].1 as usize) < self.glyph_count => self.unicode[i].1 as usize,
+                _ => self.glyph_index_raw('?'),
+            }
+        }
+        self.glyph_index_raw(c)
+    }
+
+    // fonts without a Unicode table are in code page 437 order, like the ROM ones
+    fn glyph_index_raw(&self, c: char) -> usize {
+        match CodePage::Cp437.encode_char(c) as usize {
+            i if i < self.glyph_count => i,
+            // small fonts might not even go up to '?'
+            _ if ('?' as usize) < self.glyph_count => '?' as usize,
+            _ => 0,
+        }
+    }
+
+    fn glyph(&self, index: usize) -> &[u8] {
+        let size = self.bytes_per_row * self.height;
+        &self.glyphs[index * size..(index + 1) * size]
+    }
+
+    pub fn text_width(&self, text: &str) -> usize {
+        text.chars().count() * self.width
+    }
+
+    // Draws a single glyph, a background of None leaves the unset pixels alone
+    pub fn draw_glyph(&self, display: &mut dyn Framebuffer, x: isize, y: isize, index: usize, fg: u8, bg: Option<u8>) {
+        let glyph = self.glyph(index);
+        for (row, bits) in glyph.chunks_exact(self.bytes_per_row).enumerate() {
+            for col in 0..self.width {
+                let set = bits[col / 8] & (0x80 >> (col % 8)) != 0;
+                match (set, bg) {
+                    (true, _) => display.put_pixel(x + col as isize, y + row as isize, fg),
+                    (false, Some(bg)) => display.put_pixel(x + col as isize, y + row as isize, bg),
+                    (false, None) => {}
+                }
+            }
+        }
+    }
+
+    pub fn draw_char(&self, display: &mut dyn Framebuffer, x: isize, y: isize, c: char, fg: u8, bg: Option<u8>) {
+        self.draw_glyph(display, x, y, self.glyph_index(c), fg, bg);
+    }
+
+    // Draws a line of text, returns the x position after the last character
+    pub fn draw_str(&self, display: &mut dyn Framebuffer, x: isize, y: isize, text: &str, fg: u8, bg: Option<u8>) -> isize {
+        let mut x = x;
+        for c in text.chars() {
+            self.draw_char(display, x, y, c, fg, bg);
+            x += self.width as isize;
+        }
+        x
+    }
+}
@@ -0,0 +1,220 @@
+#![allow(dead_code)]
+use alloc::vec::Vec;
+use core::arch::asm;
+
+use crate::joystick::{self, Joystick, JoystickEvent};
+use crate::keyboard::{self, KeyCode, KeyEvent};
+use crate::mouse::{self, Buttons, MouseEvent};
+use crate::timer;
+
+// Everything the keyboard, mouse, joystick and timer can tell us, in one stream
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InputEvent {
+    Key(KeyEvent),
+    Mouse(MouseEvent),
+    Joystick(JoystickEvent),
+    // the timer ticked, with the tick count
+    Tick(u32),
+}
+
+// Merges the keyboard and mouse queues with the joystick and timer
+// The keyboard, mouse handler and Timer have to be installed for their events to show up
+pub struct Input {
+    joystick: Option<Joystick>,
+    last_tick: u32,
+}
+
+impl Input {
+    pub fn new(joystick: Option<Joystick>) -> Self {
+        Self { joystick, last_tick: timer::ticks() }
+    }
+
+    pub fn joystick(&self) -> Option<&Joystick> {
+        self.joystick.as_ref()
+    }
+
+    // The next event if there is one
+    // Every tick gets its own Tick event, and the joystick is polled once per tick
+    pub fn poll(&mut self) -> Option<InputEvent> {
+        if let Some(event) = keyboard::read_event() {
+            return Some(InputEvent::Key(event));
+        }
+        if let Some(event) = mouse::read_event() {
+            return Some(InputEvent::Mouse(event));
+        }
+        if let Some(event) = self.joystick.as_mut().and_then(|j| j.read_event()) {
+            return Some(InputEvent::Joystick(event));
+        }
+        if self.last_tick != timer::ticks() {
+            self.last_tick = self.last_tick.wrapping_add(1);
+            if let Some(joystick) = &mut self.joystick {
+                joystick.poll();
+            }
+            return Some(InputEvent::Tick(self.last_tick));
+        }
+        None
+    }
+
+    // Halts until there's an event
+    pub fn wait(&mut self) -> InputEvent {
+        loop {
+            match self.poll() {
+                Some(event) => return event,
+                None => unsafe { asm!("hlt"); }
+            }
+        }
+    }
+}
+
+// A physical input that can be bound to an action
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    // one of the mouse::Buttons bits
+    MouseButton(u8),
+    WheelUp,
+    WheelDown,
+    JoystickButton(u8),
+    // stick A pushed more than halfway
+    JoystickUp,
+    JoystickDown,
+    JoystickLeft,
+    JoystickRight,
+}
+
+impl Binding {
+    // Names for config files: key names as in KeyCode, mouse_left, mouse_right,
+    // mouse_middle, wheel_up, wheel_down, joy_button1 to joy_button4, joy_up,
+    // joy_down, joy_left and joy_right
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        Some(match name.as_str() {
+            "mouse_left" => Self::MouseButton(Buttons::LEFT),
+            "mouse_right" => Self::MouseButton(Buttons::RIGHT),
+            "mouse_middle" => Self::MouseButton(Buttons::MIDDLE),
+            "wheel_up" => Self::WheelUp,
+            "wheel_down" => Self::WheelDown,
+            "joy_up" => Self::JoystickUp,
+            "joy_down" => Self::JoystickDown,
+            "joy_left" => Self::JoystickLeft,
+            "joy_right" => Self::JoystickRight,
+            _ => {
+                if let Some(n) = name.strip_prefix("joy_button") {
+                    return match n.parse::<u8>() {
+                        Ok(n @ 1..=4) => Some(Self::JoystickButton(n - 1)),
+                        _ => None,
+                    };
+                }
+                return KeyCode::from_name(&name).map(Self::Key);
+            }
+        })
+    }
+
+    // Whether this input is being held right now
+    fn is_down(self, joystick: Option<&Joystick>) -> bool {
+        let stick = |axis: usize, positive: bool| joystick.is_some_and(|j| {
+            let value = j.state().axes[axis];
+            if positive { value > joystick::AXIS_MAX / 2 } else { value < -joystick::AXIS_MAX / 2 }
+        });
+        match self {
+            Self::Key(key) => keyboard::is_down(key),
+            Self::MouseButton(button) => mouse::last_state().2.contains(button),
+            Self::WheelUp | Self::WheelDown => false,
+            Self::JoystickButton(button) => joystick.is_some_and(|j| j.state().button(button)),
+            Self::JoystickUp => stick(1, false),
+            Self::JoystickDown => stick(1, true),
+            Self::JoystickLeft => stick(0, false),
+            Self::JoystickRight => stick(0, true),
+        }
+    }
+}
+
+// The inputs an event pressed (true) or released (false)
+// Typematic repeats don't count, the key is already down
+fn bindings_of(event: &InputEvent) -> heapless::Vec<(Binding, bool), 8> {
+    let mut bindings = heapless::Vec::new();
+    match *event {
+        InputEvent::Key(event) if !event.repeat => {
+            bindings.push((Binding::Key(event.key), event.pressed)).ok();
+        }
+        InputEvent::Mouse(event) => {
+            for button in [Buttons::LEFT, Buttons::RIGHT, Buttons::MIDDLE] {
+                if event.pressed(button) { bindings.push((Binding::MouseButton(button), true)).ok(); }
+                if event.released(button) { bindings.push((Binding::MouseButton(button), false)).ok(); }
+            }
+            // the wheel doesn't stay down, so it only presses
+            if event.wheel < 0 { bindings.push((Binding::WheelUp, true)).ok(); }
+            if event.wheel > 0 { bindings.push((Binding::WheelDown, true)).ok(); }
+        }
+        InputEvent::Joystick(JoystickEvent::ButtonDown(button)) => {
+            bindings.push((Binding::JoystickButton(button), true)).ok();
+        }
+        InputEvent::Joystick(JoystickEvent::ButtonUp(button)) => {
+            bindings.push((Binding::JoystickButton(button), false)).ok();
+        }
+        _ => {}
+    }
+    bindings
+}
+
+// Maps physical inputs to actions, any number of inputs can share an action
+pub struct Bindings<A> {
+    map: Vec<(Binding, A)>,
+}
+
+impl<A: Copy + PartialEq> Bindings<A> {
+    pub fn new() -> Self {
+        Self { map: Vec::new() }
+    }
+
+    pub fn bind(&mut self, binding: Binding, action: A) {
+        self.map.retain(|&(b, _)| b != binding);
+        self.map.push((binding, action));
+    }
+
+    pub fn unbind_action(&mut self, action: A) {
+        self.map.retain(|&(_, a)| a != action);
+    }
+
+    pub fn bindings_for(&self, action: A) -> impl Iterator<Item = Binding> + '_ {
+        self.map.iter().filter(move |&&(_, a)| a == action).map(|&(b, _)| b)
+    }
+
+    // The actions an event starts (true) or ends (false)
+    pub fn actions(&self, event: &InputEvent) -> impl Iterator<Item = (A, bool)> + '_ {
+        bindings_of(event).into_iter().flat_map(move |(binding, pressed)| {
+            self.map.iter().filter(move |&&(b, _)| b == binding).map(move |&(_, a)| (a, pressed))
+        })
+    }
+
+    // Whether anything bound to the action is being held
+    pub fn is_held(&self, action: A, joystick: Option<&Joystick>) -> bool {
+        self.bindings_for(action).any(|b| b.is_down(joystick))
+    }
+
+    // Applies config lines like
+    //
+    //     # comment
+    //     zoom_in = EQUALS KP_PLUS wheel_up
+    //
+    // Each line replaces the action's bindings, with names as Binding::from_name takes them
+    // Returns the failing line number on error
+    pub fn parse(&mut self, text: &str, action_from_name: impl Fn(&str) -> Option<A>) -> Result<(), usize> {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue }
+
+            let (name, inputs) = line.split_once('=').ok_or(number + 1)?;
+            let action = action_from_name(name.trim()).ok_or(number + 1)?;
+            let inputs = inputs.split_whitespace()
+                .map(Binding::from_name)
+                .collect::<Option<Vec<_>>>()
+                .ok_or(number + 1)?;
+            self.unbind_action(action);
+            for binding in inputs {
+                self.bind(binding, action);
+            }
+        }
+        Ok(())
+    }
+}
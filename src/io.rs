@@ -0,0 +1,510 @@
+#![allow(dead_code)]
+// Read/Write/Seek in the style of std::io, which we don't get with no_std
+
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use core::fmt;
+
+use crate::dpmi::{self, DosError, File};
+pub use crate::dpmi::SeekFrom;
+
+const DEFAULT_BUF_SIZE: usize = 8192;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    Dos(DosError),
+    // the data ran out before read_exact got all it wanted
+    UnexpectedEof,
+    // a write took nothing, which for DOS means the disk is full
+    WriteZero,
+    // read_line got something that isn't UTF-8
+    InvalidUtf8,
+    // a seek to before the start
+    InvalidSeek,
+    // something wrong with the contents, like a bad header
+    InvalidData(&'static str),
+}
+
+impl From<DosError> for Error {
+    fn from(error: DosError) -> Self {
+        Self::Dos(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Dos(error) => error.fmt(f),
+            Self::UnexpectedEof => f.write_str("unexpected end of file"),
+            Self::WriteZero => f.write_str("nothing could be written"),
+            Self::InvalidUtf8 => f.write_str("invalid UTF-8"),
+            Self::InvalidSeek => f.write_str("seek before the start"),
+            Self::InvalidData(what) => f.write_str(what),
+        }
+    }
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+pub trait Read {
+    // Reads up to buf.len() bytes, 0 means the end
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf)? {
+                0 => return Err(Error::UnexpectedEof),
+                n => buf = &mut buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    // Appends everything that's left, returns how much that was
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let start = buf.len();
+        let mut chunk = [0; 512];
+        loop {
+            match self.read(&mut chunk)? {
+                0 => return Ok(buf.len() - start),
+                n => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+    }
+}
+
+pub trait Write {
+    // Writes some of buf, returns how much
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+    fn flush(&mut self) -> Result<()>;
+
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(Error::WriteZero),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    // So write!() works, keeps the first error instead of fmt's empty one
+    fn write_fmt(&mut self, args: fmt::Arguments) -> Result<()> {
+        struct Adapter<'a, W: ?Sized> {
+            inner: &'a mut W,
+            error: Result<()>,
+        }
+        impl<W: Write + ?Sized> fmt::Write for Adapter<'_, W> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.inner.write_all(s.as_bytes()).map_err(|e| {
+                    self.error = Err(e);
+                    fmt::Error
+                })
+            }
+        }
+
+        let mut adapter = Adapter { inner: self, error: Ok(()) };
+        match fmt::write(&mut adapter, args) {
+            Ok(()) => Ok(()),
+            Err(_) => adapter.error,
+        }
+    }
+}
+
+pub trait Seek {
+    // Returns the new position
+    fn seek(&mut self, pos: SeekFrom) -> Result<u32>;
+
+    fn stream_position(&mut self) -> Result<u32> {
+        self.seek(SeekFrom::Current(0))
+    }
+
+    fn rewind(&mut self) -> Result<()> {
+        self.seek(SeekFrom::Start(0))?;
+        Ok(())
+    }
+}
+
+pub trait BufRead: Read {
+    // What's buffered, reading more in if it's empty. Empty means the end
+    fn fill_buf(&mut self) -> Result<&[u8]>;
+
+    // Marks amount of the buffer as used
+    fn consume(&mut self, amount: usize);
+
+    // Appends up to and including byte, returns how many bytes that was
+    fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> Result<usize> {
+        let mut read = 0;
+        loop {
+            let (done, used) = {
+                let available = self.fill_buf()?;
+                match available.iter().position(|&b| b == byte) {
+                    Some(i) => {
+                        buf.extend_from_slice(&available[..=i]);
+                        (true, i + 1)
+                    }
+                    None => {
+                        buf.extend_from_slice(available);
+                        (available.is_empty(), available.len())
+                    }
+                }
+            };
+            self.consume(used);
+            read += used;
+            if done { return Ok(read) }
+        }
+    }
+
+    // Appends a line including its \n, returns 0 at the end
+    fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        let mut bytes = Vec::new();
+        let read = self.read_until(b'\n', &mut bytes)?;
+        buf.push_str(core::str::from_utf8(&bytes).map_err(|_| Error::InvalidUtf8)?);
+        Ok(read)
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(File::read(self, buf)? as usize)
+    }
+
+    // the size is known, so this can be one big read instead of lots of small ones
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let start = buf.len();
+        let remaining = self.get_size().saturating_sub(File::stream_position(self)) as usize;
+        buf.resize(start + remaining, 0);
+        let mut read = 0;
+        while read < remaining {
+            match File::read(self, &mut buf[start + read..])? {
+                0 => break,
+                n => read += n as usize,
+            }
+        }
+        buf.truncate(start + read);
+        Ok(read)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        Ok(File::write(self, buf)? as usize)
+    }
+
+    // DOS keeps its own buffers, File::commit is what forces those out
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u32> {
+        Ok(File::seek(self, pos)?)
+    }
+
+    fn stream_position(&mut self) -> Result<u32> {
+        Ok(File::stream_position(self))
+    }
+}
+
+impl Read for &[u8] {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = buf.len().min(self.len());
+        buf[..n].copy_from_slice(&self[..n]);
+        *self = &self[n..];
+        Ok(n)
+    }
+}
+
+impl<R: Read + ?Sized> Read for &mut R {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        (**self).read(buf)
+    }
+}
+
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        (**self).write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+impl Write for Vec<u8> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// The handles every program starts with, wherever they've been redirected to
+// These are raw bytes, print! and friends are what do the code page
+pub struct Stdin;
+pub struct Stdout;
+pub struct Stderr;
+
+pub fn stdin() -> Stdin { Stdin }
+pub fn stdout() -> Stdout { Stdout }
+pub fn stderr() -> Stderr { Stderr }
+
+impl Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(dpmi::read_handle(dpmi::STDIN, buf)? as usize)
+    }
+}
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        // after anything print! still has
+        dpmi::flush_stdout();
+        Ok(dpmi::write_handle(dpmi::STDOUT, buf)? as usize)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        dpmi::flush_stdout();
+        Ok(())
+    }
+}
+
+impl Write for Stderr {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        Ok(dpmi::write_handle(dpmi::STDERR, buf)? as usize)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Reads, seeks and writes in memory, over &[u8], Vec<u8> or anything else that's bytes
+pub struct Cursor<T> {
+    inner: T,
+    position: u32,
+}
+
+impl<T> Cursor<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner, position: 0 }
+    }
+
+    pub fn position(&self) -> u32 { self.position }
+    pub fn set_position(&mut self, position: u32) { self.position = position; }
+    pub fn get_ref(&self) -> &T { &self.inner }
+    pub fn into_inner(self) -> T { self.inner }
+}
+
+impl<T: AsRef<[u8]>> Cursor<T> {
+    fn remaining(&self) -> &[u8] {
+        let data = self.inner.as_ref();
+        &data[(self.position as usize).min(data.len())..]
+    }
+}
+
+impl<T: AsRef<[u8]>> Read for Cursor<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = self.remaining().read(buf)?;
+        self.position += n as u32;
+        Ok(n)
+    }
+}
+
+impl<T: AsRef<[u8]>> BufRead for Cursor<T> {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        Ok(self.remaining())
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position += amount as u32;
+    }
+}
+
+impl<T: AsRef<[u8]>> Seek for Cursor<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u32> {
+        let position = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::Current(offset) => self.position as i64 + offset as i64,
+            SeekFrom::End(offset) => self.inner.as_ref().len() as i64 + offset as i64,
+        };
+        if position < 0 { return Err(Error::InvalidSeek) }
+        self.position = position as u32;
+        Ok(self.position)
+    }
+}
+
+// Writing past the end grows the Vec, filling any gap with zeros
+impl Write for Cursor<Vec<u8>> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let start = self.position as usize;
+        let end = start + buf.len();
+        if self.inner.len() < end {
+            self.inner.resize(end, 0);
+        }
+        self.inner[start..end].copy_from_slice(buf);
+        self.position = end as u32;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Fixed size, writes stop at the end of the slice
+impl Write for Cursor<&mut [u8]> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let start = (self.position as usize).min(self.inner.len());
+        let n = buf.len().min(self.inner.len() - start);
+        self.inner[start..start + n].copy_from_slice(&buf[..n]);
+        self.position = (start + n) as u32;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Reads big chunks from inner, so lots of small reads don't each go to DOS
+pub struct BufReader<R> {
+    inner: R,
+    buf: Box<[u8]>,
+    // buf[pos..filled] is what hasn't been read yet
+    pos: usize,
+    filled: usize,
+}
+
+impl<R: Read> BufReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self { inner, buf: vec![0; capacity.max(1)].into_boxed_slice(), pos: 0, filled: 0 }
+    }
+
+    pub fn get_ref(&self) -> &R { &self.inner }
+    pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+    // Anything still buffered is lost
+    pub fn into_inner(self) -> R { self.inner }
+
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+}
+
+impl<R: Read> Read for BufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // big reads skip the buffer when it's empty anyway
+        if self.pos == self.filled && buf.len() >= self.buf.len() {
+            return self.inner.read(buf);
+        }
+        let n = self.fill_buf()?.read(buf)?;
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for BufReader<R> {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        if self.pos == self.filled {
+            self.filled = self.inner.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.filled);
+    }
+}
+
+impl<R: Read + Seek> Seek for BufReader<R> {
+    // the inner position is ahead by whatever is still buffered
+    fn seek(&mut self, pos: SeekFrom) -> Result<u32> {
+        let buffered = (self.filled - self.pos) as i32;
+        let pos = match pos {
+            SeekFrom::Current(offset) => SeekFrom::Current(offset - buffered),
+            pos => pos,
+        };
+        self.pos = 0;
+        self.filled = 0;
+        self.inner.seek(pos)
+    }
+
+    fn stream_position(&mut self) -> Result<u32> {
+        Ok(self.inner.stream_position()? - (self.filled - self.pos) as u32)
+    }
+}
+
+// Collects small writes into bigger ones, flushed when full, on flush() and on drop
+// Errors on drop are lost, so flush() first when they matter
+pub struct BufWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> BufWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        Self { inner, buf: Vec::with_capacity(capacity.max(1)) }
+    }
+
+    pub fn get_ref(&self) -> &W { &self.inner }
+
+    fn flush_buf(&mut self) -> Result<()> {
+        let result = self.inner.write_all(&self.buf);
+        self.buf.clear();
+        result
+    }
+
+    // Flushes and gives back the writer
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush_buf()?;
+        // Drop would flush again, so take inner out without running it
+        let this = core::mem::ManuallyDrop::new(self);
+        let inner = unsafe { core::ptr::read(&this.inner) };
+        drop(unsafe { core::ptr::read(&this.buf) });
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for BufWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if self.buf.len() + buf.len() > self.buf.capacity() {
+            self.flush_buf()?;
+        }
+        // too big to be worth buffering
+        if buf.len() >= self.buf.capacity() {
+            return self.inner.write(buf);
+        }
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.flush_buf()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + Seek> Seek for BufWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u32> {
+        self.flush_buf()?;
+        self.inner.seek(pos)
+    }
+}
+
+impl<W: Write> Drop for BufWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush_buf();
+    }
+}
@@ -0,0 +1,257 @@
+#![allow(dead_code)]
+use alloc::collections::VecDeque;
+use core::arch::asm;
+
+use crate::timer::{self, PIT_FREQUENCY};
+use crate::vga::{inb, outb};
+
+const GAME_PORT: u16 = 0x201;
+// stick A x/y, stick B x/y
+pub const AXES: usize = 4;
+// read_raw mask for looking at every axis, bit n is axis n
+pub const ALL_AXES: u8 = 0xf;
+pub const BUTTONS: usize = 4;
+// what a fully deflected axis normalizes to
+pub const AXIS_MAX: i32 = 32767;
+// an axis that hasn't come back after 5ms has nothing plugged in
+// this is also the longest interrupts stay off for a read
+const TIMEOUT: u32 = PIT_FREQUENCY / 200;
+// axis values have to change by this much (of AXIS_MAX) to make an event
+const AXIS_EVENT_THRESHOLD: i32 = AXIS_MAX / 64;
+
+// Latches and reads PIT channel 0's count
+fn pit_count() -> u16 {
+    unsafe {
+        outb(0x43, 0x00);
+        let low = inb(0x40);
+        let high = inb(0x40);
+        u16::from_le_bytes([low, high])
+    }
+}
+
+// Buttons held down, bit 0 is A1, then A2, B1, B2
+// The port has them active low in bits 4-7
+pub fn read_buttons() -> u8 {
+    (!unsafe { inb(GAME_PORT) } >> 4) & 0xf
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct RawState {
+    // PIT clocks each axis' one-shot took to time out, None if it never did
+    pub axes: [Option<u32>; AXES],
+    pub buttons: u8,
+}
+
+// Fires the game port's one-shots and times how long each axis bit stays high
+// The time is set by the stick's resistance charging a capacitor, so it goes up with the position
+// Only the axes in `mask` are waited for, axes with nothing plugged in never come back
+// and would hold interrupts off for the whole timeout
+pub fn read_raw(mask: u8) -> RawState {
+    let mut axes = [None; AXES];
+    // the PIT runs in mode 3, which counts down by 2 through the divisor twice per period
+    let divisor = timer::pit_divisor();
+    let flags: u32;
+    unsafe { asm!(
+        "pushfd",
+        "pop {}",
+        "cli",
+        out(reg) flags
+    ); }
+
+    // elapsed time adds up read by read, so it doesn't matter how often the count wraps
+    let mut elapsed = 0;
+    let mut last = pit_count();
+    unsafe { outb(GAME_PORT, 0xff); }
+    loop {
+        let bits = unsafe { inb(GAME_PORT) };
+        let count = pit_count();
+        elapsed += if count <= last { (last - count) as u32 } else { last as u32 + divisor - count as u32 };
+        last = count;
+
+        let clocks = elapsed / 2;
+        for (axis, value) in axes.iter_mut().enumerate() {
+            if value.is_none() && mask & (1 << axis) != 0 && bits & (1 << axis) == 0 {
+                *value = Some(clocks);
+            }
+        }
+        if bits & mask == 0 || clocks > TIMEOUT { break }
+    }
+
+    // interrupts back on only if they were on before
+    if flags & 0x200 != 0 {
+        unsafe { asm!("sti"); }
+    }
+    RawState { axes, buttons: read_buttons() }
+}
+
+// Where an axis reads at its ends and when left alone
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct AxisCalibration {
+    pub min: u32,
+    pub center: u32,
+    pub max: u32,
+    // readings this close to center count as centered, in AXIS_MAX units
+    pub dead_zone: i32,
+}
+
+impl AxisCalibration {
+    // From a reading with the stick let go, the range starts out as a guess
+    // and grows with extend as the stick gets moved around
+    pub fn from_center(center: u32) -> Self {
+        Self { min: center / 2, center, max: center * 2, dead_zone: AXIS_MAX / 10 }
+    }
+
+    pub fn extend(&mut self, raw: u32) {
+        self.min = self.min.min(raw);
+        self.max = self.max.max(raw);
+    }
+
+    // Scales a raw reading to -AXIS_MAX..=AXIS_MAX, 0 at center
+    pub fn normalize(&self, raw: u32) -> i32 {
+        let value = if raw < self.center {
+            let range = (self.center - self.min).max(1) as i64;
+            -((self.center - raw.max(self.min)) as i64 * AXIS_MAX as i64 / range)
+        } else {
+            let range = (self.max - self.center).max(1) as i64;
+            (raw.min(self.max) - self.center) as i64 * AXIS_MAX as i64 / range
+        } as i32;
+
+        // stretch what's left past the dead zone back out to the full range
+        if value.abs() <= self.dead_zone { return 0 }
+        let live = (value.abs() - self.dead_zone) as i64 * AXIS_MAX as i64 / (AXIS_MAX - self.dead_zone).max(1) as i64;
+        live as i32 * value.signum()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum JoystickEvent {
+    ButtonDown(u8),
+    ButtonUp(u8),
+    // normalized value of an axis that moved
+    Axis { axis: u8, value: i32 },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct JoystickState {
+    // normalized, 0 for axes that aren't there
+    pub axes: [i32; AXES],
+    pub buttons: u8,
+}
+
+impl JoystickState {
+    pub fn button(&self, button: u8) -> bool {
+        self.buttons & (1 << button) != 0
+    }
+}
+
+// There's no interrupt for the game port, so the state gets polled
+// and the changes queued up like the keyboard and mouse events
+pub struct Joystick {
+    // None for axes that didn't answer when we detected
+    pub calibration: [Option<AxisCalibration>; AXES],
+    state: JoystickState,
+    // axis values the last Axis events had
+    reported: [i32; AXES],
+    events: VecDeque<JoystickEvent>,
+}
+
+impl Joystick {
+    // Calibrates the center from where the sticks are now, so they should be let go
+    // None if no axis answered
+    pub fn detect() -> Option<Self> {
+        let raw = read_raw(ALL_AXES);
+        if raw.axes.iter().all(|a| a.is_none()) { return None }
+        let mut joystick = Self {
+            calibration: raw.axes.map(|a| a.map(AxisCalibration::from_center)),
+            state: JoystickState::default(),
+            reported: [0; AXES],
+            events: VecDeque::new(),
+        };
+        joystick.state.buttons = raw.buttons;
+        Some(joystick)
+    }
+
+    // Axes that answered when we detected, as a read_raw mask
+    pub fn axis_mask(&self) -> u8 {
+        self.calibration.iter().enumerate()
+            .filter(|(_, calibration)| calibration.is_some())
+            .fold(0, |mask, (axis, _)| mask | 1 << axis)
+    }
+
+    pub fn calibrate_center(&mut self) {
+        let raw = read_raw(self.axis_mask());
+        for (calibration, value) in self.calibration.iter_mut().zip(raw.axes) {
+            if let (Some(calibration), Some(value)) = (calibration.as_mut(), value) {
+                calibration.center = value;
+                calibration.extend(value);
+            }
+        }
+    }
+
+    // Sets the range from the extremes seen until done() says to stop,
+    // the stick should be moved around all its corners meanwhile
+    // Reads once per tick, so the Timer has to be installed
+    pub fn calibrate_range(&mut self, mut done: impl FnMut() -> bool) {
+        let mut ranges: [Option<(u32, u32)>; AXES] = [None; AXES];
+        let mask = self.axis_mask();
+        while !done() {
+            for (range, value) in ranges.iter_mut().zip(read_raw(mask).axes) {
+                if let Some(value) = value {
+                    let (min, max) = range.get_or_insert((value, value));
+                    *min = (*min).min(value);
+                    *max = (*max).max(value);
+                }
+            }
+            timer::wait_tick();
+        }
+        for (calibration, range) in self.calibration.iter_mut().zip(ranges) {
+            if let (Some(calibration), Some((min, max))) = (calibration.as_mut(), range) {
+                calibration.min = min.min(calibration.center);
+                calibration.max = max.max(calibration.center);
+            }
+        }
+    }
+
+    // Reads the port, queues events for whatever changed and returns the new state
+    pub fn poll(&mut self) -> JoystickState {
+        let raw = read_raw(self.axis_mask());
+        let mut state = JoystickState { axes: [0; AXES], buttons: raw.buttons };
+        for (axis, (calibration, value)) in self.calibration.iter_mut().zip(raw.axes).enumerate() {
+            if let (Some(calibration), Some(value)) = (calibration.as_mut(), value) {
+                calibration.extend(value);
+                state.axes[axis] = calibration.normalize(value);
+            }
+        }
+
+        for button in 0..BUTTONS as u8 {
+            match (self.state.button(button), state.button(button)) {
+                (false, true) => self.events.push_back(JoystickEvent::ButtonDown(button)),
+                (true, false) => self.events.push_back(JoystickEvent::ButtonUp(button)),
+                _ => {}
+            }
+        }
+        for (axis, (reported, &new)) in self.reported.iter_mut().zip(state.axes.iter()).enumerate() {
+            // always report getting back to exactly center
+            if (new - *reported).abs() >= AXIS_EVENT_THRESHOLD || (new == 0 && *reported != 0) {
+                self.events.push_back(JoystickEvent::Axis { axis: axis as u8, value: new });
+                *reported = new;
+            }
+        }
+        self.state = state;
+        state
+    }
+
+    // State as of the last poll
+    pub fn state(&self) -> JoystickState {
+        self.state
+    }
+
+    // Takes the oldest event out of the queue
+    pub fn read_event(&mut self) -> Option<JoystickEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn clear_events(&mut self) {
+        self.events.clear();
+    }
+}
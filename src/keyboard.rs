@@ -0,0 +1,573 @@
+#![allow(dead_code)]
+use alloc::string::String;
+use core::arch::asm;
+use core::sync::atomic::{AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+
+use crate::dpmi::IntHandler;
+use crate::keymap::{Entry, Keymap};
+
+// Key codes are scan code set 1 make codes, with 0x80 added for the E0 prefixed keys
+// so they can't clash with plain scan codes, whatever the keyboard sends
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct KeyCode(pub u8);
+
+impl KeyCode {
+    pub const ESCAPE: Self = Self(0x01);
+    pub const KEY_1: Self = Self(0x02);
+    pub const KEY_2: Self = Self(0x03);
+    pub const KEY_3: Self = Self(0x04);
+    pub const KEY_4: Self = Self(0x05);
+    pub const KEY_5: Self = Self(0x06);
+    pub const KEY_6: Self = Self(0x07);
+    pub const KEY_7: Self = Self(0x08);
+    pub const KEY_8: Self = Self(0x09);
+    pub const KEY_9: Self = Self(0x0a);
+    pub const KEY_0: Self = Self(0x0b);
+    pub const MINUS: Self = Self(0x0c);
+    pub const EQUALS: Self = Self(0x0d);
+    pub const BACKSPACE: Self = Self(0x0e);
+    pub const TAB: Self = Self(0x0f);
+    pub const Q: Self = Self(0x10);
+    pub const W: Self = Self(0x11);
+    pub const E: Self = Self(0x12);
+    pub const R: Self = Self(0x13);
+    pub const T: Self = Self(0x14);
+    pub const Y: Self = Self(0x15);
+    pub const U: Self = Self(0x16);
+    pub const I: Self = Self(0x17);
+    pub const O: Self = Self(0x18);
+    pub const P: Self = Self(0x19);
+    pub const LEFT_BRACKET: Self = Self(0x1a);
+    pub const RIGHT_BRACKET: Self = Self(0x1b);
+    pub const ENTER: Self = Self(0x1c);
+    pub const LEFT_CTRL: Self = Self(0x1d);
+    pub const A: Self = Self(0x1e);
+    pub const S: Self = Self(0x1f);
+    pub const D: Self = Self(0x20);
+    pub const F: Self = Self(0x21);
+    pub const G: Self = Self(0x22);
+    pub const H: Self = Self(0x23);
+    pub const J: Self = Self(0x24);
+    pub const K: Self = Self(0x25);
+    pub const L: Self = Self(0x26);
+    pub const SEMICOLON: Self = Self(0x27);
+    pub const APOSTROPHE: Self = Self(0x28);
+    pub const GRAVE: Self = Self(0x29);
+    pub const LEFT_SHIFT: Self = Self(0x2a);
+    pub const BACKSLASH: Self = Self(0x2b);
+    pub const Z: Self = Self(0x2c);
+    pub const X: Self = Self(0x2d);
+    pub const C: Self = Self(0x2e);
+    pub const V: Self = Self(0x2f);
+    pub const B: Self = Self(0x30);
+    pub const N: Self = Self(0x31);
+    pub const M: Self = Self(0x32);
+    pub const COMMA: Self = Self(0x33);
+    pub const PERIOD: Self = Self(0x34);
+    pub const SLASH: Self = Self(0x35);
+    pub const RIGHT_SHIFT: Self = Self(0x36);
+    pub const KP_MULTIPLY: Self = Self(0x37);
+    pub const LEFT_ALT: Self = Self(0x38);
+    pub const SPACE: Self = Self(0x39);
+    pub const CAPS_LOCK: Self = Self(0x3a);
+    pub const F1: Self = Self(0x3b);
+    pub const F2: Self = Self(0x3c);
+    pub const F3: Self = Self(0x3d);
+    pub const F4: Self = Self(0x3e);
+    pub const F5: Self = Self(0x3f);
+    pub const F6: Self = Self(0x40);
+    pub const F7: Self = Self(0x41);
+    pub const F8: Self = Self(0x42);
+    pub const F9: Self = Self(0x43);
+    pub const F10: Self = Self(0x44);
+    pub const NUM_LOCK: Self = Self(0x45);
+    pub const SCROLL_LOCK: Self = Self(0x46);
+    pub const KP_7: Self = Self(0x47);
+    pub const KP_8: Self = Self(0x48);
+    pub const KP_9: Self = Self(0x49);
+    pub const KP_MINUS: Self = Self(0x4a);
+    pub const KP_4: Self = Self(0x4b);
+    pub const KP_5: Self = Self(0x4c);
+    pub const KP_6: Self = Self(0x4d);
+    pub const KP_PLUS: Self = Self(0x4e);
+    pub const KP_1: Self = Self(0x4f);
+    pub const KP_2: Self = Self(0x50);
+    pub const KP_3: Self = Self(0x51);
+    pub const KP_0: Self = Self(0x52);
+    pub const KP_PERIOD: Self = Self(0x53);
+    // the extra key next to left shift on 102 key keyboards
+    pub const NON_US_BACKSLASH: Self = Self(0x56);
+    pub const F11: Self = Self(0x57);
+    pub const F12: Self = Self(0x58);
+
+    // E0 prefixed keys
+    pub const KP_ENTER: Self = Self(0x9c);
+    pub const RIGHT_CTRL: Self = Self(0x9d);
+    pub const KP_DIVIDE: Self = Self(0xb5);
+    pub const RIGHT_ALT: Self = Self(0xb8);
+    pub const HOME: Self = Self(0xc7);
+    pub const UP: Self = Self(0xc8);
+    pub const PAGE_UP: Self = Self(0xc9);
+    pub const LEFT: Self = Self(0xcb);
+    pub const RIGHT: Self = Self(0xcd);
+    pub const END: Self = Self(0xcf);
+    pub const DOWN: Self = Self(0xd0);
+    pub const PAGE_DOWN: Self = Self(0xd1);
+    pub const INSERT: Self = Self(0xd2);
+    pub const DELETE: Self = Self(0xd3);
+    pub const LEFT_GUI: Self = Self(0xdb);
+    pub const RIGHT_GUI: Self = Self(0xdc);
+    pub const MENU: Self = Self(0xdd);
+    pub const PRINT_SCREEN: Self = Self(0xb7);
+    // E1 prefixed, E1 1D 45 goes where E0 45 would
+    pub const PAUSE: Self = Self(0xc5);
+
+    fn from_extended(scancode: u8) -> Option<Self> {
+        match scancode {
+            // E0 2A and E0 36 are the fake shifts sent around the grey keys
+            0x2a | 0x36 => None,
+            _ => Some(Self(0x80 | scancode)),
+        }
+    }
+
+    // Looks up a key by its constant's name, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        NAMES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|&(_, key)| key)
+    }
+
+    pub fn name(self) -> Option<&'static str> {
+        NAMES.iter().find(|&&(_, key)| key == self).map(|&(n, _)| n)
+    }
+
+    pub fn is_keypad(self) -> bool {
+        (Self::KP_7..=Self::KP_PERIOD).contains(&self) || self == Self::KP_MULTIPLY
+            || self == Self::KP_ENTER || self == Self::KP_DIVIDE
+    }
+}
+
+// for config files, the same names as the constants
+const NAMES: [(&str, KeyCode); 105] = [
+    ("ESCAPE", KeyCode::ESCAPE),
+    ("KEY_1", KeyCode::KEY_1),
+    ("KEY_2", KeyCode::KEY_2),
+    ("KEY_3", KeyCode::KEY_3),
+    ("KEY_4", KeyCode::KEY_4),
+    ("KEY_5", KeyCode::KEY_5),
+    ("KEY_6", KeyCode::KEY_6),
+    ("KEY_7", KeyCode::KEY_7),
+    ("KEY_8", KeyCode::KEY_8),
+    ("KEY_9", KeyCode::KEY_9),
+    ("KEY_0", KeyCode::KEY_0),
+    ("MINUS", KeyCode::MINUS),
+    ("EQUALS", KeyCode::EQUALS),
+    ("BACKSPACE", KeyCode::BACKSPACE),
+    ("TAB", KeyCode::TAB),
+    ("Q", KeyCode::Q),
+    ("W", KeyCode::W),
+    ("E", KeyCode::E),
+    ("R", KeyCode::R),
+    ("T", KeyCode::T),
+    ("Y", KeyCode::Y),
+    ("U", KeyCode::U),
+    ("I", KeyCode::I),
+    ("O", KeyCode::O),
+    ("P", KeyCode::P),
+    ("LEFT_BRACKET", KeyCode::LEFT_BRACKET),
+    ("RIGHT_BRACKET", KeyCode::RIGHT_BRACKET),
+    ("ENTER", KeyCode::ENTER),
+    ("LEFT_CTRL", KeyCode::LEFT_CTRL),
+    ("A", KeyCode::A),
+    ("S", KeyCode::S),
+    ("D", KeyCode::D),
+    ("F", KeyCode::F),
+    ("G", KeyCode::G),
+    ("H", KeyCode::H),
+    ("J", KeyCode::J),
+    ("K", KeyCode::K),
+    ("L", KeyCode::L),
+    ("SEMICOLON", KeyCode::SEMICOLON),
+    ("APOSTROPHE", KeyCode::APOSTROPHE),
+    ("GRAVE", KeyCode::GRAVE),
+    ("LEFT_SHIFT", KeyCode::LEFT_SHIFT),
+    ("BACKSLASH", KeyCode::BACKSLASH),
+    ("Z", KeyCode::Z),
+    ("X", KeyCode::X),
+    ("C", KeyCode::C),
+    ("V", KeyCode::V),
+    ("B", KeyCode::B),
+    ("N", KeyCode::N),
+    ("M", KeyCode::M),
+    ("COMMA", KeyCode::COMMA),
+    ("PERIOD", KeyCode::PERIOD),
+    ("SLASH", KeyCode::SLASH),
+    ("RIGHT_SHIFT", KeyCode::RIGHT_SHIFT),
+    ("KP_MULTIPLY", KeyCode::KP_MULTIPLY),
+    ("LEFT_ALT", KeyCode::LEFT_ALT),
+    ("SPACE", KeyCode::SPACE),
+    ("CAPS_LOCK", KeyCode::CAPS_LOCK),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("NUM_LOCK", KeyCode::NUM_LOCK),
+    ("SCROLL_LOCK", KeyCode::SCROLL_LOCK),
+    ("KP_7", KeyCode::KP_7),
+    ("KP_8", KeyCode::KP_8),
+    ("KP_9", KeyCode::KP_9),
+    ("KP_MINUS", KeyCode::KP_MINUS),
+    ("KP_4", KeyCode::KP_4),
+    ("KP_5", KeyCode::KP_5),
+    ("KP_6", KeyCode::KP_6),
+    ("KP_PLUS", KeyCode::KP_PLUS),
+    ("KP_1", KeyCode::KP_1),
+    ("KP_2", KeyCode::KP_2),
+    ("KP_3", KeyCode::KP_3),
+    ("KP_0", KeyCode::KP_0),
+    ("KP_PERIOD", KeyCode::KP_PERIOD),
+    ("NON_US_BACKSLASH", KeyCode::NON_US_BACKSLASH),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12),
+    ("KP_ENTER", KeyCode::KP_ENTER),
+    ("RIGHT_CTRL", KeyCode::RIGHT_CTRL),
+    ("KP_DIVIDE", KeyCode::KP_DIVIDE),
+    ("RIGHT_ALT", KeyCode::RIGHT_ALT),
+    ("HOME", KeyCode::HOME),
+    ("UP", KeyCode::UP),
+    ("PAGE_UP", KeyCode::PAGE_UP),
+    ("LEFT", KeyCode::LEFT),
+    ("RIGHT", KeyCode::RIGHT),
+    ("END", KeyCode::END),
+    ("DOWN", KeyCode::DOWN),
+    ("PAGE_DOWN", KeyCode::PAGE_DOWN),
+    ("INSERT", KeyCode::INSERT),
+    ("DELETE", KeyCode::DELETE),
+    ("LEFT_GUI", KeyCode::LEFT_GUI),
+    ("RIGHT_GUI", KeyCode::RIGHT_GUI),
+    ("MENU", KeyCode::MENU),
+    ("PRINT_SCREEN", KeyCode::PRINT_SCREEN),
+    ("PAUSE", KeyCode::PAUSE),
+];
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Modifiers(pub u16);
+
+impl Modifiers {
+    pub const LEFT_SHIFT: u16 = 0x001;
+    pub const RIGHT_SHIFT: u16 = 0x002;
+    pub const LEFT_CTRL: u16 = 0x004;
+    pub const RIGHT_CTRL: u16 = 0x008;
+    pub const LEFT_ALT: u16 = 0x010;
+    pub const RIGHT_ALT: u16 = 0x020;
+    pub const CAPS_LOCK: u16 = 0x040;
+    pub const NUM_LOCK: u16 = 0x080;
+    pub const SCROLL_LOCK: u16 = 0x100;
+
+    pub fn contains(self, flags: u16) -> bool { self.0 & flags != 0 }
+    pub fn shift(self) -> bool { self.contains(Self::LEFT_SHIFT | Self::RIGHT_SHIFT) }
+    pub fn ctrl(self) -> bool { self.contains(Self::LEFT_CTRL | Self::RIGHT_CTRL) }
+    pub fn alt(self) -> bool { self.contains(Self::LEFT_ALT | Self::RIGHT_ALT) }
+    pub fn caps_lock(self) -> bool { self.contains(Self::CAPS_LOCK) }
+    pub fn num_lock(self) -> bool { self.contains(Self::NUM_LOCK) }
+    pub fn scroll_lock(self) -> bool { self.contains(Self::SCROLL_LOCK) }
+
+    fn for_key(key: KeyCode) -> u16 {
+        match key {
+            KeyCode::LEFT_SHIFT => Self::LEFT_SHIFT,
+            KeyCode::RIGHT_SHIFT => Self::RIGHT_SHIFT,
+            KeyCode::LEFT_CTRL => Self::LEFT_CTRL,
+            KeyCode::RIGHT_CTRL => Self::RIGHT_CTRL,
+            KeyCode::LEFT_ALT => Self::LEFT_ALT,
+            KeyCode::RIGHT_ALT => Self::RIGHT_ALT,
+            _ => 0,
+        }
+    }
+
+    fn lock_for_key(key: KeyCode) -> u16 {
+        match key {
+            KeyCode::CAPS_LOCK => Self::CAPS_LOCK,
+            KeyCode::NUM_LOCK => Self::NUM_LOCK,
+            KeyCode::SCROLL_LOCK => Self::SCROLL_LOCK,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct KeyEvent {
+    pub key: KeyCode,
+    pub pressed: bool,
+    // typematic repeat of a key that was already down
+    pub repeat: bool,
+    // modifier state after this event
+    pub modifiers: Modifiers,
+}
+
+impl KeyEvent {
+    // packed as key | pressed << 8 | repeat << 9 | modifiers << 16, for the queue
+    fn pack(self) -> u32 {
+        self.key.0 as u32 | (self.pressed as u32) << 8 | (self.repeat as u32) << 9 | (self.modifiers.0 as u32) << 16
+    }
+
+    fn unpack(v: u32) -> Self {
+        Self {
+            key: KeyCode(v as u8),
+            pressed: v & 0x100 != 0,
+            repeat: v & 0x200 != 0,
+            modifiers: Modifiers((v >> 16) as u16),
+        }
+    }
+}
+
+const QUEUE_LEN: usize = 64;
+// ring buffer of packed KeyEvents, the interrupt handler only writes at QUEUE_HEAD
+// and readers only move QUEUE_TAIL, so no locking is needed
+static QUEUE: [AtomicU32; QUEUE_LEN] = [const { AtomicU32::new(0) }; QUEUE_LEN];
+static QUEUE_HEAD: AtomicUsize = AtomicUsize::new(0);
+static QUEUE_TAIL: AtomicUsize = AtomicUsize::new(0);
+
+// one bit per key code
+static KEY_DOWN: [AtomicU32; 8] = [const { AtomicU32::new(0) }; 8];
+static MODIFIERS: AtomicU16 = AtomicU16::new(0);
+
+// 0, 0xE0, or 0xE1 while inside a prefixed sequence
+static PREFIX: AtomicU8 = AtomicU8::new(0);
+// bytes left in an E1 sequence
+static PREFIX_LEFT: AtomicU8 = AtomicU8::new(0);
+
+pub fn is_down(key: KeyCode) -> bool {
+    let key = key.0 as usize;
+    KEY_DOWN[key / 32].load(Ordering::Relaxed) & (1 << (key % 32)) != 0
+}
+
+pub fn modifiers() -> Modifiers {
+    Modifiers(MODIFIERS.load(Ordering::Relaxed))
+}
+
+// Takes the oldest event out of the queue
+pub fn read_event() -> Option<KeyEvent> {
+    let tail = QUEUE_TAIL.load(Ordering::Relaxed);
+    if tail == QUEUE_HEAD.load(Ordering::Acquire) {
+        return None;
+    }
+    let event = QUEUE[tail].load(Ordering::Relaxed);
+    QUEUE_TAIL.store((tail + 1) % QUEUE_LEN, Ordering::Release);
+    Some(KeyEvent::unpack(event))
+}
+
+// Waits for the next key press, halting in between
+pub fn wait_press() -> KeyEvent {
+    loop {
+        match read_event() {
+            Some(event) if event.pressed => return event,
+            Some(_) => {}
+            None => unsafe { asm!("hlt"); }
+        }
+    }
+}
+
+pub fn clear_events() {
+    QUEUE_TAIL.store(QUEUE_HEAD.load(Ordering::Acquire), Ordering::Release);
+}
+
+// Translation happens outside the interrupt handler, so this only needs to be a spin lock
+static KEYMAP: spin::Mutex<Option<Keymap>> = spin::Mutex::new(None);
+// accent of the dead key waiting for its next key, if any
+static DEAD_KEY: spin::Mutex<Option<char>> = spin::Mutex::new(None);
+
+// Switches the layout used for translating keys, US is used until this is called
+pub fn set_keymap(keymap: Keymap) {
+    *KEYMAP.lock() = Some(keymap);
+    *DEAD_KEY.lock() = None;
+}
+
+pub fn keymap_name() -> String {
+    KEYMAP.lock().get_or_insert_with(Keymap::us).name.clone()
+}
+
+// The characters a key press types with the current layout
+// A dead key gives nothing, the key after it gives the combined character,
+// or the accent and then the character if they don't combine
+pub fn translate(event: KeyEvent) -> heapless::Vec<char, 2> {
+    let mut chars = heapless::Vec::new();
+    if !event.pressed { return chars }
+
+    let mut keymap = KEYMAP.lock();
+    let keymap = keymap.get_or_insert_with(Keymap::us);
+    let mut dead = DEAD_KEY.lock();
+    match (keymap.lookup(event.key, event.modifiers), *dead) {
+        (Entry::None, _) => {}
+        (Entry::Dead(accent), None) => *dead = Some(accent),
+        // the same dead key twice types the accent
+        (Entry::Dead(accent), Some(pending)) => {
+            *dead = None;
+            chars.push(pending).ok();
+            if accent != pending { *dead = Some(accent); }
+        }
+        (Entry::Char(c), None) => { chars.push(c).ok(); }
+        (Entry::Char(c), Some(accent)) => {
+            *dead = None;
+            match keymap.combine(accent, c) {
+                Some(combined) => { chars.push(combined).ok(); }
+                None if c == ' ' => { chars.push(accent).ok(); }
+                None => {
+                    chars.push(accent).ok();
+                    chars.push(c).ok();
+                }
+            }
+        }
+    }
+    chars
+}
+
+// Waits for a key press that types something, and returns the first character
+pub fn read_char() -> char {
+    loop {
+        if let Some(&c) = translate(wait_press()).first() {
+            return c;
+        }
+    }
+}
+
+fn push_event(event: KeyEvent) {
+    let head = QUEUE_HEAD.load(Ordering::Relaxed);
+    let next = (head + 1) % QUEUE_LEN;
+    // full, drop the event rather than the oldest one
+    if next == QUEUE_TAIL.load(Ordering::Acquire) { return }
+    QUEUE[head].store(event.pack(), Ordering::Relaxed);
+    QUEUE_HEAD.store(next, Ordering::Release);
+}
+
+fn key_changed(key: KeyCode, pressed: bool) {
+    let (word, bit) = (key.0 as usize / 32, 1 << (key.0 % 32));
+    let was_down = KEY_DOWN[word].load(Ordering::Relaxed) & bit != 0;
+    if pressed {
+        KEY_DOWN[word].fetch_or(bit, Ordering::Relaxed);
+    } else {
+        KEY_DOWN[word].fetch_and(!bit, Ordering::Relaxed);
+    }
+
+    let mut modifiers = MODIFIERS.load(Ordering::Relaxed);
+    match (pressed, Modifiers::for_key(key)) {
+        (_, 0) => {}
+        (true, m) => modifiers |= m,
+        (false, m) => modifiers &= !m,
+    }
+    // locks toggle on the first press only, not the repeats
+    if pressed && !was_down {
+        modifiers ^= Modifiers::lock_for_key(key);
+    }
+    MODIFIERS.store(modifiers, Ordering::Relaxed);
+
+    push_event(KeyEvent { key, pressed, repeat: pressed && was_down, modifiers: Modifiers(modifiers) });
+}
+
+fn handle_scancode(code: u8) {
+    match code {
+        // controller responses and overruns, not keys
+        0x00 | 0xfa | 0xfe | 0xff => return,
+        0xe0 | 0xe1 => {
+            PREFIX.store(code, Ordering::Relaxed);
+            // Pause is E1 1D 45 E1 9D C5, with no real release
+            PREFIX_LEFT.store(if code == 0xe1 { 2 } else { 0 }, Ordering::Relaxed);
+            return;
+        }
+        _ => {}
+    }
+
+    let pressed = code & 0x80 == 0;
+    let scancode = code & 0x7f;
+    let key = match PREFIX.load(Ordering::Relaxed) {
+        0xe0 => {
+            PREFIX.store(0, Ordering::Relaxed);
+            match KeyCode::from_extended(scancode) {
+                Some(key) => key,
+                None => return,
+            }
+        }
+        0xe1 => {
+            let left = PREFIX_LEFT.load(Ordering::Relaxed).saturating_sub(1);
+            PREFIX_LEFT.store(left, Ordering::Relaxed);
+            if left > 0 { return }
+            PREFIX.store(0, Ordering::Relaxed);
+            if pressed {
+                key_changed(KeyCode::PAUSE, true);
+                key_changed(KeyCode::PAUSE, false);
+            }
+            return;
+        }
+        _ => KeyCode(scancode),
+    };
+    key_changed(key, pressed);
+}
+
+pub extern "x86-interrupt" fn keyboard_int_handler() {
+    let old_ds: u16;
+    unsafe { asm!(
+        "mov bx, ds",
+        "mov ax, es",
+        "mov ds, ax",
+        out("bx") old_ds
+    ); }
+
+    let code: u8;
+    unsafe { asm!(
+        "in al, 0x60",
+        out("al") code
+    ); }
+
+    handle_scancode(code);
+
+    unsafe { asm!(
+        "mov ds, bx",
+        "out 0x20, al",
+        in("al") 0x20_u8,
+        in("bx") old_ds
+    ); }
+}
+
+// Owns the IRQ1 handler while it exists, the old one is put back on drop
+pub struct Keyboard {
+    handler: IntHandler,
+}
+
+impl Keyboard {
+    pub fn install() -> Self {
+        // start from the lock states the BIOS has, in the flags byte at 0x417
+        let bios_flags = unsafe { *(0x417 as *const u8) };
+        let mut modifiers = 0;
+        if bios_flags & 0x10 != 0 { modifiers |= Modifiers::SCROLL_LOCK; }
+        if bios_flags & 0x20 != 0 { modifiers |= Modifiers::NUM_LOCK; }
+        if bios_flags & 0x40 != 0 { modifiers |= Modifiers::CAPS_LOCK; }
+        MODIFIERS.store(modifiers, Ordering::Relaxed);
+        for word in KEY_DOWN.iter() {
+            word.store(0, Ordering::Relaxed);
+        }
+        PREFIX.store(0, Ordering::Relaxed);
+        clear_events();
+
+        let mut handler = IntHandler::new(9);
+        handler.set_handler(keyboard_int_handler);
+        Self { handler }
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        self.handler.restore_handler();
+        // hand our lock states back to the BIOS
+        let lock_bits = {
+            let m = modifiers();
+            (m.scroll_lock() as u8) << 4 | (m.num_lock() as u8) << 5 | (m.caps_lock() as u8) << 6
+        };
+        unsafe {
+            let flags = 0x417 as *mut u8;
+            *flags = *flags & !0x70 | lock_bits;
+        }
+    }
+}
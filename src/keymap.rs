@@ -0,0 +1,361 @@
+#![allow(dead_code)]
+use alloc::{string::String, vec, vec::Vec};
+use core::ffi::CStr;
+use core::fmt;
+
+use crate::dpmi;
+use crate::io::{self, Read};
+use crate::keyboard::{KeyCode, Modifiers};
+
+// Keyboard layouts, as text:
+//
+//     # comment
+//     name German
+//     0x10 q Q @
+//     0x0d dead:´ dead:`
+//     dead ^ a â
+//
+// A key line is the scan code, then what it types normally, with Shift,
+// with AltGr and with Shift+AltGr. Each one is a single character, `space`,
+// `U+XXXX`, `dead:X` for a dead key with accent X, or `none`.
+// Missing entries are none. Layouts start out as US and only list what differs.
+// `dead` lines add to the built-in dead key combinations.
+
+// key codes with a table entry, the rest of the keys don't type anything
+const TABLE_KEYS: usize = 0x59;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Entry {
+    None,
+    Char(char),
+    Dead(char),
+}
+
+pub struct Keymap {
+    pub name: String,
+    // normal, Shift, AltGr, Shift+AltGr
+    keys: Vec<[Entry; 4]>,
+    // (accent, base, result)
+    dead: Vec<(char, char, char)>,
+}
+
+const US: &str = "\
+name US
+0x01 U+001B U+001B
+0x02 1 !
+0x03 2 @
+0x04 3 #
+0x05 4 $
+0x06 5 %
+0x07 6 ^
+0x08 7 &
+0x09 8 *
+0x0a 9 (
+0x0b 0 )
+0x0c - _
+0x0d = +
+0x0e U+0008 U+0008
+0x0f U+0009 U+0009
+0x10 q Q
+0x11 w W
+0x12 e E
+0x13 r R
+0x14 t T
+0x15 y Y
+0x16 u U
+0x17 i I
+0x18 o O
+0x19 p P
+0x1a [ {
+0x1b ] }
+0x1c U+000D U+000D
+0x1e a A
+0x1f s S
+0x20 d D
+0x21 f F
+0x22 g G
+0x23 h H
+0x24 j J
+0x25 k K
+0x26 l L
+0x27 ; :
+0x28 ' \"
+0x29 ` ~
+0x2b \\ |
+0x2c z Z
+0x2d x X
+0x2e c C
+0x2f v V
+0x30 b B
+0x31 n N
+0x32 m M
+0x33 , <
+0x34 . >
+0x35 / ?
+0x37 * *
+0x39 space space
+0x47 7
+0x48 8
+0x49 9
+0x4a - -
+0x4b 4
+0x4c 5
+0x4d 6
+0x4e + +
+0x4f 1
+0x50 2
+0x51 3
+0x52 0
+0x53 .
+0x56 \\ |
+";
+
+const UK: &str = "\
+name UK
+0x03 2 \"
+0x04 3 £
+0x05 4 $ €
+0x28 ' @
+0x29 ` ¬ ¦
+0x2b # ~
+0x56 \\ |
+";
+
+const DE: &str = "\
+name German
+0x29 dead:^ °
+0x03 2 \" ²
+0x04 3 § ³
+0x07 6 &
+0x08 7 / {
+0x09 8 ( [
+0x0a 9 ) ]
+0x0b 0 = }
+0x0c ß ? \\
+0x0d dead:´ dead:`
+0x10 q Q @
+0x12 e E €
+0x15 z Z
+0x1a ü Ü
+0x1b + * ~
+0x27 ö Ö
+0x28 ä Ä
+0x2b # '
+0x2c y Y
+0x32 m M µ
+0x33 , ;
+0x34 . :
+0x35 - _
+0x56 < > |
+";
+
+const FR: &str = "\
+name French
+0x29 ² none
+0x02 & 1
+0x03 é 2 ~
+0x04 \" 3 #
+0x05 ' 4 {
+0x06 ( 5 [
+0x07 - 6 |
+0x08 è 7 `
+0x09 _ 8 \\
+0x0a ç 9 ^
+0x0b à 0 @
+0x0c ) ° ]
+0x0d = + }
+0x10 a A
+0x11 z Z
+0x12 e E €
+0x1a dead:^ dead:¨
+0x1b $ £ ¤
+0x1e q Q
+0x27 m M
+0x28 ù %
+0x2b * µ
+0x2c w W
+0x32 , ?
+0x33 ; .
+0x34 : /
+0x35 ! §
+0x56 < >
+";
+
+pub const BUILT_IN: [&str; 4] = ["US", "UK", "DE", "FR"];
+
+// (accent, characters it combines with, what they become)
+const DEAD_KEYS: [(char, &str, &str); 5] = [
+    ('^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ('´', "aeiouyAEIOUY", "áéíóúýÁÉÍÓÚÝ"),
+    ('¨', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+    ('~', "anoANO", "ãñõÃÑÕ"),
+];
+
+fn parse_entry(token: &str) -> Option<Entry> {
+    let mut chars = token.chars();
+    let first = chars.next()?;
+    if chars.next().is_none() {
+        return Some(Entry::Char(first));
+    }
+    match token {
+        "none" => Some(Entry::None),
+        "space" => Some(Entry::Char(' ')),
+        _ => {
+            if let Some(hex) = token.strip_prefix("U+") {
+                return char::from_u32(u32::from_str_radix(hex, 16).ok()?).map(Entry::Char);
+            }
+            let mut accent = token.strip_prefix("dead:")?.chars();
+            match (accent.next(), accent.next()) {
+                (Some(c), None) => Some(Entry::Dead(c)),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum KeymapError {
+    Io(io::Error),
+    NotUtf8,
+    // the line number, counting from 1
+    BadLine(usize),
+}
+
+impl From<io::Error> for KeymapError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "Could not read keyboard layout: {}", error),
+            Self::NotUtf8 => write!(f, "Keyboard layout is not UTF-8"),
+            Self::BadLine(line) => write!(f, "Bad keyboard layout line {}", line),
+        }
+    }
+}
+
+fn single_char(token: Option<&str>) -> Option<char> {
+    let mut chars = token?.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+impl Keymap {
+    fn empty() -> Self {
+        let mut dead = Vec::new();
+        for (accent, bases, results) in DEAD_KEYS {
+            dead.extend(bases.chars().zip(results.chars()).map(|(b, r)| (accent, b, r)));
+        }
+        Self { name: String::new(), keys: vec![[Entry::None; 4]; TABLE_KEYS], dead }
+    }
+
+    pub fn us() -> Self {
+        let mut map = Self::empty();
+        map.apply(US).unwrap();
+        map
+    }
+
+    // One of BUILT_IN, by name
+    pub fn built_in(name: &str) -> Option<Self> {
+        let layout = match name.to_ascii_uppercase().as_str() {
+            "US" => return Some(Self::us()),
+            "UK" | "GB" => UK,
+            "DE" | "GR" => DE,
+            "FR" => FR,
+            _ => return None,
+        };
+        Some(Self::parse(layout).unwrap())
+    }
+
+    // Parses a layout in the text format, on top of US
+    pub fn parse(text: &str) -> Result<Self, KeymapError> {
+        let mut map = Self::us();
+        map.apply(text).map_err(KeymapError::BadLine)?;
+        Ok(map)
+    }
+
+    pub fn load(path: &CStr) -> Result<Self, KeymapError> {
+        let mut file = dpmi::File::open(path).map_err(io::Error::from)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Self::parse(core::str::from_utf8(&data).map_err(|_| KeymapError::NotUtf8)?)
+    }
+
+    // applies a layout's lines, returns the failing line number on error
+    fn apply(&mut self, text: &str) -> Result<(), usize> {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue }
+
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("name") => {
+                    self.name = line["name".len()..].trim().into();
+                }
+                Some("dead") => {
+                    let accent = single_char(tokens.next()).ok_or(number + 1)?;
+                    let base = single_char(tokens.next()).ok_or(number + 1)?;
+                    let result = single_char(tokens.next()).ok_or(number + 1)?;
+                    self.dead.retain(|&(a, b, _)| (a, b) != (accent, base));
+                    self.dead.push((accent, base, result));
+                }
+                Some(code) => {
+                    let code = code.strip_prefix("0x").ok_or(number + 1)?;
+                    let code = usize::from_str_radix(code, 16).map_err(|_| number + 1)?;
+                    if code >= TABLE_KEYS { return Err(number + 1) }
+                    let mut entries = [Entry::None; 4];
+                    for (entry, token) in entries.iter_mut().zip(tokens.by_ref()) {
+                        *entry = parse_entry(token).ok_or(number + 1)?;
+                    }
+                    if tokens.next().is_some() { return Err(number + 1) }
+                    self.keys[code] = entries;
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    // What the key gives with these modifiers, without any dead key handling
+    pub fn lookup(&self, key: KeyCode, modifiers: Modifiers) -> Entry {
+        match key {
+            KeyCode::KP_ENTER => return Entry::Char('\r'),
+            KeyCode::KP_DIVIDE => return Entry::Char('/'),
+            // without Num Lock (or with Shift) the keypad is cursor keys
+            k if k.is_keypad() && k != KeyCode::KP_MULTIPLY && k != KeyCode::KP_MINUS && k != KeyCode::KP_PLUS => {
+                if modifiers.num_lock() == modifiers.shift() { return Entry::None }
+                return self.keys[key.0 as usize][0];
+            }
+            _ => {}
+        }
+        let entries = match self.keys.get(key.0 as usize) {
+            Some(entries) => entries,
+            None => return Entry::None,
+        };
+
+        // Ctrl+Alt works as AltGr on keyboards without one
+        let altgr = modifiers.contains(Modifiers::RIGHT_ALT) || (modifiers.ctrl() && modifiers.alt());
+        if !altgr && (modifiers.ctrl() || modifiers.alt()) {
+            return Entry::None;
+        }
+
+        // Caps Lock works like Shift on keys where Shift gives the upper case letter
+        let mut shift = modifiers.shift();
+        if let (Entry::Char(lower), Entry::Char(upper)) = (entries[0], entries[1]) {
+            if modifiers.caps_lock() && !altgr && lower.is_alphabetic() && lower.to_uppercase().eq(core::iter::once(upper)) {
+                shift = !shift;
+            }
+        }
+        entries[shift as usize | (altgr as usize) << 1]
+    }
+
+    pub fn combine(&self, accent: char, base: char) -> Option<char> {
+        self.dead.iter().find(|&&(a, b, _)| a == accent && b == base).map(|&(_, _, r)| r)
+    }
+}
@@ -12,12 +12,89 @@ mod dpmi_alloc;
 mod panic;
 mod vga;
 mod bmp;
+mod codepage;
+mod ansi;
+mod args;
+mod console;
+mod cursor;
+mod env;
+mod font;
+mod input;
+mod io;
+mod joystick;
+mod keyboard;
+mod keymap;
+mod mouse;
+mod palette;
+mod readline;
+mod timer;
+mod ui;
 
-use alloc::{vec, ffi::CString};
+use alloc::{format, string::String, vec, vec::Vec};
+use args::Args;
 use bmp::Bmp;
+use console::{Attribute, Console, Rect};
+use cursor::{SoftwareCursor, Sprite};
+use dpmi::{Attributes, DosError};
+use font::{Font, RomFont};
+use joystick::Joystick;
+use keymap::Keymap;
+use input::{Bindings, Input, InputEvent};
+use io::{BufReader, BufWriter, Cursor, Read};
+use ui::ListBox;
+use keyboard::Keyboard;
+use mouse::{EventMask, Mouse};
+use timer::Timer;
 use vga::Mode13hDisplay;
 
 const TEST_BMP: &[u8; 5318] = include_bytes!("chicken.bmp");
+// half a second at 70Hz
+const FADE_FRAMES: u32 = 35;
+// options in here go before the ones on the command line
+const OPTIONS_VAR: &str = "VIEWER";
+// goes after "Usage: PROGRAM"
+const USAGE: &str = "\
+ [options] [file or pattern...] [@response file]
+
+  Shows BMP images and ANSI art, several files or a pattern like *.BMP
+  make a slideshow. With no files, a file browser comes up.
+
+  /zoom:N     start zoomed in N times, 1 to 8
+  /delay:N    seconds each slideshow image stays up, 5 by default
+  /keys:NAME  keyboard layout, US, UK, DE, FR or a layout file
+  /?          this help
+
+  Options can also be SET in the VIEWER environment variable.";
+
+// What the command line can change
+struct Options {
+    zoom: usize,
+    // seconds each image of a slideshow stays up
+    delay: u32,
+    // built-in keyboard layout name or layout file, US if not given
+    keys: Option<String>,
+}
+
+impl Options {
+    // None if help was asked for
+    fn parse(args: &Args) -> Result<Option<Self>, String> {
+        let mut options = Options { zoom: 1, delay: 5, keys: None };
+        for (name, value) in args.switches() {
+            let number = |range: core::ops::RangeInclusive<u32>| value
+                .and_then(|v| v.parse::<u32>().ok())
+                .filter(|n| range.contains(n))
+                .ok_or_else(|| format!("/{} needs a number from {} to {}.", name, range.start(), range.end()));
+            match name.to_ascii_lowercase().as_str() {
+                "?" | "h" | "help" => return Ok(None),
+                "zoom" => options.zoom = number(1..=8)? as usize,
+                "delay" => options.delay = number(1..=3600)?,
+                "keys" => options.keys = Some(value.ok_or("/keys needs a layout name or file.")?.into()),
+                _ => return Err(format!("Unknown option /{}.", name)),
+            }
+        }
+        Ok(Some(options))
+    }
+}
 
 #[no_mangle]
 pub extern "C" fn start() {
@@ -27,137 +104,554 @@ pub extern "C" fn start() {
         "pop es",
     ); }
     main();
+    // hand the cursor back to DOS if we were still printing to the console
+    drop(console::redirect_print(None));
     dpmi::dpmi_exit();
 }
 
 fn main() {
-    let args = dpmi::get_args();
-    let filename = {
-        if args.is_empty() || args[0].is_empty() {
-            println!("Filename required.");
-            println!("Press Q to exit, or any key to continue with the default image.");
-            match dpmi::getchar() as u8 {
-                b'q' | b'Q' => return,
-                _ => None
-            }
-        } else {
-            Some(&*args[0])
+    console::redirect_print(Some(Console::new()));
+
+    let command_line = format!("{} {}", env::var(OPTIONS_VAR).unwrap_or_default(), codepage::decode(&dpmi::command_tail()));
+    let args = match Args::parse(&command_line) {
+        Ok(args) => args,
+        Err(e) => {
+            println!("{}.", e);
+            return;
         }
     };
-
-    // Try to load BMP file from filename, or else use the included test image
-    let bmp = {
-        let mut bmp_buff;
-        let src = if let Some(filename) = filename {
-            println!("Loading BMP from {}...", filename);
-            let mut file = match dpmi::File::open(&CString::new(filename).unwrap()) {
-                Some(f) => f,
-                None => {
-                    println!("Could not open file.");
-                    return;
-                }
-            };
-            println!("File size: {} bytes", file.get_size());
-
-            bmp_buff = vec![0; file.get_size() as usize];
-            file.read(&mut bmp_buff);
-
-            &bmp_buff[..]
-        } else {
-            TEST_BMP
+    let options = match Options::parse(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("Usage: {}{}", program_name(), USAGE);
+            return;
+        }
+        Err(e) => {
+            println!("{}\n\nUsage: {}{}", e, program_name(), USAGE);
+            return;
+        }
+    };
+    if let Some(keys) = &options.keys {
+        // built-in names first, anything else is a file
+        let keymap = match Keymap::built_in(keys) {
+            Some(keymap) => Ok(keymap),
+            None => Keymap::load(&codepage::to_cstring(keys)),
         };
-        if let Some(bmp) = bmp::load_bmp(src) {
-            bmp
+        match keymap {
+            Ok(keymap) => keyboard::set_keymap(keymap),
+            Err(e) => eprintln!("{}, using the US layout.", e),
+        }
+    }
+
+    // None stands for the built-in image
+    let mut images: Vec<Option<String>> = Vec::new();
+    for name in args.values() {
+        // wildcards make a slideshow of everything that matches
+        if name.contains(['*', '?']) {
+            let files = matching_files(name);
+            if files.is_empty() {
+                eprintln!("No files match {}.", name);
+            }
+            images.extend(files.into_iter().map(Some));
         } else {
-            println!("Could not open BMP. Exiting");
+            images.push(Some(name.into()));
+        }
+    }
+    if images.is_empty() {
+        // patterns that matched nothing have already said so
+        if args.values().next().is_some() { return }
+        match choose_file() {
+            Some(choice) => images.push(choice),
+            None => return,
+        }
+    }
+    let slideshow = images.len() > 1;
+
+    // ANSI art goes to the text console instead
+    if let [Some(filename)] = &images[..] {
+        if filename.to_ascii_uppercase().ends_with(".ANS") {
+            show_ans(filename);
             return;
         }
+    }
+
+    // the first image that loads, unloadable ones get skipped
+    let (mut index, mut bmp) = {
+        let mut first = None;
+        for (i, image) in images.iter().enumerate() {
+            println!("Loading BMP from {}...", image_name(image));
+            match read_image(image.as_deref()) {
+                Ok(bmp) => {
+                    first = Some((i, bmp));
+                    break;
+                }
+                Err(e) => eprintln!("Could not load {}: {}.", image_name(image), e),
+            }
+        }
+        match first {
+            Some(first) => first,
+            None => {
+                eprintln!("Could not open BMP. Exiting");
+                return;
+            }
+        }
     };
 
     println!("Width x Height x BPP:   {}x{}x{}", bmp.header.width, bmp.header.height, bmp.header.bpp);
     println!("Colors Used, Important: {},{}", bmp.header.colors_used, bmp.header.colors_important);
-    println!("Arrow keys to move, 1-9 to change speed, Q to exit.");
+    println!("\x1b[1;36mArrow keys, drag or joystick to move, 1-9 to change speed, +/-, wheel or joystick buttons to zoom, Q to exit.\x1b[0m");
+    if slideshow {
+        println!("\x1b[1;36m{} images, PgDn/Space and PgUp/Backspace to change, or wait {} seconds.\x1b[0m", images.len(), options.delay);
+    }
+    let bindings = load_bindings();
     println!("Press any key to continue.");
     dpmi::getchar();
+    drop(console::redirect_print(None));
 
     // mode 13h, 320x200 256 color graphics
     dpmi::set_video_mode(0x13);
     // Get screen buffer
     let mut vga = Mode13hDisplay::default();
 
-    // sets the VGA screen palette to the BMP color palette
-    vga::set_vga_dac_colors(0, &bmp.palette_table);
+    let mut status = StatusBar::new(image_name(&images[index]), &bmp);
+
+    // draw the first frame in black, then fade in to the BMP color palette
+    vga::set_vga_dac_colors(0, &[palette::BLACK; 256]);
+    let mut pos = Position { x: 0, y: 0 };
+    draw_loop(&mut vga, &bmp, &pos, options.zoom, &status);
+    palette::fade_from_black(0, &bmp.palette_table, FADE_FRAMES);
 
     // set up new keyboard handler
     // could do getchar, but this is more fun
-    let mut kb_handler = dpmi::IntHandler::new(9);
-    kb_handler.set_handler(keyboard_int_handler);
+    let kb = Keyboard::install();
+    let timer = Timer::install();
+    // the driver has to be reset after the mode switch to pick up mode 13h
+    let mut mouse = Mouse::detect();
+    if let Some(mouse) = &mut mouse {
+        mouse.install_handler(EventMask::ALL);
+        // the driver's own cursor stays hidden, we draw ours over the back buffer
+        vga.set_cursor(Some(SoftwareCursor::new(Sprite::arrow(status.bg, status.fg))));
+        let (x, y, _) = mouse.state();
+        vga.move_cursor(x as isize / 2, y as isize);
+    }
+    // the stick has to be let go while the center gets calibrated
+    let mut input = Input::new(Joystick::detect());
+    // where the mouse is, in screen pixels
+    let mut mouse_pos = (0, 0);
+    if let Some(mouse) = &mouse {
+        let (x, y, _) = mouse.state();
+        mouse_pos = (x as isize / 2, y as isize);
+    }
 
-    let mut last_scancode = 0xFF;
     let mut delta = 1;
-    let mut pos = Position { x: 0, y: 0 };
-    loop {
-        let scancode = { *SCANCODE.read() };
-        if scancode != last_scancode {
-            match scancode {
-                0x48 => { pos.y += delta; }, // up
-                0x4B => { pos.x -= delta; }, // left
-                0x4D => { pos.x += delta; }, // right
-                0x50 => { pos.y -= delta; }, // down
-                s @ 0x02..=0x0A => { delta = s as isize - 1; }, // 1-9
-                0x10 => break, // q
-                _ => {}
+    let mut zoom = options.zoom;
+    // how many ticks the movement keys have been held for
+    let mut held_ticks = 0;
+    // how long the current slide has been up
+    let mut slide_ticks = 0;
+    'viewer: loop {
+        let mut redraw = false;
+        // images to move through the list by
+        let mut step = 0;
+        // take everything that's queued up, so a burst of events only redraws once
+        while let Some(event) = input.poll() {
+            if let InputEvent::Mouse(event) = event {
+                // the driver's x goes up to 639 in mode 13h as well
+                let (x, y) = (event.x as isize / 2, event.y as isize);
+                vga.move_cursor(x, y);
+                if event.moved() && bindings.is_held(Action::Drag, input.joystick()) {
+                    pos.x += x - mouse_pos.0;
+                    pos.y += y - mouse_pos.1;
+                    redraw = true;
+                }
+                mouse_pos = (x, y);
+            }
+
+            for (action, pressed) in bindings.actions(&event) {
+                if !pressed { continue }
+                // zooming with the mouse keeps the point under the cursor in place
+                let around = matches!(event, InputEvent::Mouse(_)).then_some(mouse_pos);
+                match action {
+                    Action::Speed(speed) => { delta = speed as isize; },
+                    Action::ZoomIn => zoom_view(&mut pos, &mut zoom, 1, around),
+                    Action::ZoomOut => zoom_view(&mut pos, &mut zoom, -1, around),
+                    Action::Screenshot => { save_screenshot(&vga); continue },
+                    Action::NextImage => step = 1,
+                    Action::PreviousImage => step = -1,
+                    Action::Quit => break 'viewer,
+                    _ => continue,
+                }
+                redraw = true;
+            }
+
+            if !matches!(event, InputEvent::Tick(_)) { continue }
+            slide_ticks += 1;
+            if slideshow && slide_ticks >= options.delay * timer::TICKS_PER_SECOND {
+                step = 1;
+            }
+            // the stick moves at a speed that goes with how far it's pushed
+            if let Some(joystick) = input.joystick() {
+                let axes = joystick.state().axes;
+                let (jx, jy) = (axes[0] as isize, -axes[1] as isize);
+                let speed = delta * 4;
+                pos.x += jx * speed / joystick::AXIS_MAX as isize;
+                pos.y += jy * speed / joystick::AXIS_MAX as isize;
+                redraw |= jx != 0 || jy != 0;
             }
-            last_scancode = scancode;
-            draw_loop(&mut vga, &bmp, &pos);
+
+            // movement polls the key state every tick, so keys can be held and combined
+            let (dx, dy) = movement_direction(&bindings, input.joystick());
+            if dx == 0 && dy == 0 {
+                held_ticks = 0;
+                continue;
+            }
+            held_ticks += 1;
+            let step = delta * acceleration(held_ticks);
+            pos.x += dx * step;
+            pos.y += dy * step;
+            redraw = true;
+        }
+
+        if step != 0 && images.len() > 1 {
+            palette::fade_to_black(0, &palette::save(), FADE_FRAMES);
+            if let Some((i, next)) = next_image(&images, index, step) {
+                index = i;
+                bmp = next;
+                status = StatusBar::new(image_name(&images[index]), &bmp);
+                if mouse.is_some() {
+                    vga.set_cursor(Some(SoftwareCursor::new(Sprite::arrow(status.bg, status.fg))));
+                    vga.move_cursor(mouse_pos.0, mouse_pos.1);
+                }
+                pos = Position { x: 0, y: 0 };
+                zoom = options.zoom;
+            }
+            slide_ticks = 0;
+            draw_loop(&mut vga, &bmp, &pos, zoom, &status);
+            palette::fade_from_black(0, &bmp.palette_table, FADE_FRAMES);
+            continue;
+        }
+        if redraw {
+            draw_loop(&mut vga, &bmp, &pos, zoom, &status);
         }
         // halt processor so we don't burn the CPU
         unsafe { asm!("hlt"); }
     }
 
-    // restore old keyboard handler
-    kb_handler.restore_handler();
+    // restore old mouse, timer and keyboard handlers
+    drop(mouse);
+    drop(timer);
+    drop(kb);
+
+    palette::fade_to_black(0, &palette::save(), FADE_FRAMES);
 
     // mode 3h, text mode graphics, DOS default
     dpmi::set_video_mode(0x3);
 }
 
-fn draw_loop(vga: &mut Mode13hDisplay, bmp: &Bmp, pos: &Position) {
+fn show_ans(filename: &str) {
+    let mut file = match dpmi::File::open(&codepage::to_cstring(filename)) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Could not open {}: {}.", filename, e);
+            return;
+        }
+    };
+    let mut data = Vec::new();
+    if let Err(e) = file.read_to_end(&mut data) {
+        eprintln!("Could not read {}: {}.", filename, e);
+        return;
+    }
+
+    console::with_print_console(|c| {
+        ansi::display_ans(c, &data);
+        c.attr = Attribute::default();
+    });
+    dpmi::getchar();
+}
+
+// The built-in image doesn't have a file name
+fn image_name(image: &Option<String>) -> &str {
+    image.as_deref().unwrap_or("(built-in)")
+}
+
+// Loads a BMP file, or the built-in image for None
+fn read_image(filename: Option<&str>) -> io::Result<Bmp> {
+    match filename {
+        Some(filename) => {
+            let file = dpmi::File::open(&codepage::to_cstring(filename))?;
+            bmp::read_bmp(&mut BufReader::new(file))
+        }
+        None => bmp::read_bmp(&mut Cursor::new(&TEST_BMP[..])),
+    }
+}
+
+// Goes step images from index, skipping any that don't load
+// None if nothing else loads
+fn next_image(images: &[Option<String>], index: usize, step: isize) -> Option<(usize, Bmp)> {
+    let count = images.len() as isize;
+    (1..count)
+        .map(|n| (index as isize + step * n).rem_euclid(count) as usize)
+        .find_map(|i| read_image(images[i].as_deref()).ok().map(|bmp| (i, bmp)))
+}
+
+// The part of a path before the file name, with its separator
+fn directory_of(path: &str) -> &str {
+    let end = path.rfind(['\\', '/', ':']).map_or(0, |i| i + 1);
+    &path[..end]
+}
+
+// Files matching a wildcard pattern, with the pattern's directory in front and sorted
+fn matching_files(pattern: &str) -> Vec<String> {
+    let directory = directory_of(pattern);
+    let mut files: Vec<String> = dpmi::find_files(&codepage::to_cstring(pattern), Attributes::NONE)
+        .filter_map(|entry| entry.ok())
+        .map(|entry| format!("{}{}", directory, entry.name))
+        .collect();
+    files.sort();
+    files
+}
+
+// Lets the user pick a BMP or ANS file, going through directories as they go
+// Returns None if cancelled, Some(None) for the built-in image
+fn choose_file() -> Option<Option<String>> {
+    const BUILT_IN: &str = "(built-in image)";
+    let mut directory = String::new();
+    loop {
+        let pattern = codepage::to_cstring(&format!("{}*.*", directory));
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+        for entry in dpmi::find_files(&pattern, Attributes::DIRECTORY).filter_map(|entry| entry.ok()) {
+            let upper = entry.name.to_ascii_uppercase();
+            if entry.is_dir() {
+                if entry.name != "." { dirs.push(format!("{}\\", entry.name)); }
+            } else if upper.ends_with(".BMP") || upper.ends_with(".ANS") {
+                files.push(entry.name);
+            }
+        }
+        dirs.sort();
+        files.sort();
+        let mut items = vec![String::from(BUILT_IN)];
+        items.append(&mut dirs);
+        items.append(&mut files);
+
+        let choice = console::with_console_taken(|c| {
+            let rect = ui::centered(c, 40, 20);
+            let title = if directory.is_empty() { String::from("Open") } else { format!("Open {}", directory) };
+            let mut list = ListBox::new(Rect { x: 0, y: 0, width: rect.width - 2, height: rect.height - 2 }, items);
+            let mut dialog = ui::Dialog::new(rect, &title);
+            dialog.add(&mut list);
+            match dialog.run(c, &mut ui::BiosKeyboard) {
+                ui::DialogResult::Activated(_) => list.selected_item().map(String::from),
+                ui::DialogResult::Cancelled => None,
+            }
+        }).flatten()?;
+
+        if choice == BUILT_IN { return Some(None) }
+        match choice.strip_suffix('\\') {
+            // going up past where we started needs another ..
+            Some("..") if directory.is_empty() || directory.ends_with("..\\") => directory.push_str("..\\"),
+            Some("..") => {
+                directory.pop();
+                directory.truncate(directory_of(&directory).len());
+            }
+            Some(_) => directory.push_str(&choice),
+            None => return Some(Some(format!("{}{}", directory, choice))),
+        }
+    }
+}
+
+fn movement_direction(bindings: &Bindings<Action>, joystick: Option<&Joystick>) -> (isize, isize) {
+    let held = |action| bindings.is_held(action, joystick) as isize;
+    let dx = held(Action::MoveRight) - held(Action::MoveLeft);
+    let dy = held(Action::MoveUp) - held(Action::MoveDown);
+    (dx, dy)
+}
+
+// changes the zoom by step, keeping the image point at around where it is
+fn zoom_view(pos: &mut Position, zoom: &mut usize, step: isize, around: Option<(isize, isize)>) {
+    let new_zoom = (*zoom as isize + step).clamp(1, 8) as usize;
+    if let Some((x, y)) = around {
+        pos.x = x - (x - pos.x) * new_zoom as isize / *zoom as isize;
+        pos.y = y - (y - pos.y) * new_zoom as isize / *zoom as isize;
+    }
+    *zoom = new_zoom;
+}
+
+fn save_screenshot(vga: &Mode13hDisplay) -> Option<()> {
+    // the first number that isn't taken yet
+    let path = (0..10000)
+        .map(|n| codepage::to_cstring(&format!("SHOT{:04}.BMP", n)))
+        .find(|path| matches!(dpmi::File::open(path), Err(DosError::FileNotFound)))?;
+    let mut out = BufWriter::new(dpmi::File::create(&path).ok()?);
+    bmp::save_bmp(&mut out, 320, 200, vga.pixels(), &palette::save()).ok()?;
+    out.into_inner().ok()?.close().ok()
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    ZoomIn,
+    ZoomOut,
+    // move step, 1 to 9
+    Speed(u8),
+    // moving the mouse while this is held pans the image
+    Drag,
+    // saves what's on screen to the next free SHOTnnnn.BMP
+    Screenshot,
+    // in a slideshow
+    NextImage,
+    PreviousImage,
+    Quit,
+}
+
+impl Action {
+    fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        Some(match name.as_str() {
+            "move_up" => Self::MoveUp,
+            "move_down" => Self::MoveDown,
+            "move_left" => Self::MoveLeft,
+            "move_right" => Self::MoveRight,
+            "zoom_in" => Self::ZoomIn,
+            "zoom_out" => Self::ZoomOut,
+            "drag" => Self::Drag,
+            "screenshot" => Self::Screenshot,
+            "next_image" => Self::NextImage,
+            "previous_image" => Self::PreviousImage,
+            "quit" => Self::Quit,
+            _ => match name.strip_prefix("speed_")?.parse() {
+                Ok(speed @ 1..=9) => Self::Speed(speed),
+                _ => return None,
+            }
+        })
+    }
+}
+
+// lines in BINDINGS_FILE replace these, action by action
+const DEFAULT_BINDINGS: &str = "\
+move_up = UP KP_8
+move_down = DOWN KP_2
+move_left = LEFT KP_4
+move_right = RIGHT KP_6
+zoom_in = EQUALS KP_PLUS wheel_up joy_button1
+zoom_out = MINUS KP_MINUS wheel_down joy_button2
+drag = mouse_left
+screenshot = F12
+next_image = PAGE_DOWN SPACE
+previous_image = PAGE_UP BACKSPACE
+quit = Q ESCAPE
+speed_1 = KEY_1
+speed_2 = KEY_2
+speed_3 = KEY_3
+speed_4 = KEY_4
+speed_5 = KEY_5
+speed_6 = KEY_6
+speed_7 = KEY_7
+speed_8 = KEY_8
+speed_9 = KEY_9
+";
+const BINDINGS_FILE: &str = "VIEWKEYS.CFG";
+
+fn load_bindings() -> Bindings<Action> {
+    let mut bindings = Bindings::new();
+    bindings.parse(DEFAULT_BINDINGS, Action::from_name).unwrap();
+
+    let mut file = match open_config(BINDINGS_FILE) {
+        Ok(f) => f,
+        // no file just means the defaults
+        Err(DosError::FileNotFound) => return bindings,
+        Err(e) => {
+            eprintln!("Could not open {}: {}, using the default keys.", BINDINGS_FILE, e);
+            return bindings;
+        }
+    };
+    let mut data = Vec::new();
+    if let Err(e) = file.read_to_end(&mut data) {
+        eprintln!("Could not read {}: {}, using the default keys.", BINDINGS_FILE, e);
+        return bindings;
+    }
+    let text = match core::str::from_utf8(&data) {
+        Ok(text) => text,
+        Err(_) => {
+            println!("{} is not UTF-8, using the default keys.", BINDINGS_FILE);
+            return bindings;
+        }
+    };
+    if let Err(line) = bindings.parse(text, Action::from_name) {
+        println!("Bad key binding on line {} of {}, the rest is ignored.", line, BINDINGS_FILE);
+    }
+    bindings
+}
+
+// Config files are looked for in the current directory, then next to the program
+fn open_config(name: &str) -> Result<dpmi::File, DosError> {
+    match dpmi::File::open(&codepage::to_cstring(name)) {
+        Err(DosError::FileNotFound) => {}
+        result => return result,
+    }
+    let program = env::program_path().ok_or(DosError::FileNotFound)?;
+    dpmi::File::open(&codepage::to_cstring(&format!("{}{}", directory_of(&program), name)))
+}
+
+// What we were run as, without the directory or extension
+fn program_name() -> String {
+    let path = env::program_path().unwrap_or_else(|| String::from("VIEWER"));
+    let name = &path[directory_of(&path).len()..];
+    String::from(name.split('.').next().unwrap_or(name))
+}
+
+// movement speeds up the longer it's held, by one step every half second up to 4x
+fn acceleration(held_ticks: u32) -> isize {
+    (1 + held_ticks / (timer::TICKS_PER_SECOND / 2)).min(4) as isize
+}
+
+fn draw_loop(vga: &mut Mode13hDisplay, bmp: &Bmp, pos: &Position, zoom: usize, status: &StatusBar) {
     vga.clear();
-    vga.copy_to_screen(pos.x, pos.y, bmp.header.width as usize, bmp.header.height as usize, &bmp.data);
-    // TODO wait for blanking interval
+    vga.copy_to_screen_scaled(pos.x, pos.y, bmp.header.width as usize, bmp.header.height as usize, zoom, &bmp.data);
+    status.draw(vga, zoom);
+    vga::wait_vertical_retrace();
     vga.flush();
 }
 
-struct Position {
-    x: isize,
-    y: isize,
+struct StatusBar {
+    font: Font,
+    text: String,
+    fg: u8,
+    bg: u8,
 }
 
-static SCANCODE: spin::RwLock<u8> = spin::RwLock::new(0);
-pub extern "x86-interrupt" fn keyboard_int_handler() {
-    let old_ds: u16;
-    unsafe { asm!(
-        "mov bx, ds",
-        "mov ax, es",
-        "mov ds, ax",
-        out("bx") old_ds
-    ); }
+impl StatusBar {
+    fn new(filename: &str, bmp: &Bmp) -> Self {
+        // the palette is whatever the BMP brought, so use its brightest and darkest colors
+        let brightness = |i: &usize| {
+            let c = bmp.palette_table[*i];
+            c.red as u32 + c.green as u32 + c.blue as u32
+        };
+        let colors = 0..bmp.palette_table.len();
+        let fg = colors.clone().max_by_key(brightness).unwrap_or(15) as u8;
+        let bg = colors.min_by_key(brightness).unwrap_or(0) as u8;
 
-    let code: u8;
-    unsafe { asm!(
-        "in al, 0x60",
-        out("al") code
-    ); }
+        Self {
+            font: Font::rom(RomFont::Font8x8),
+            text: format!("{}  {}x{}", filename, bmp.header.width, bmp.header.height),
+            fg,
+            bg,
+        }
+    }
 
-    let mut scan = SCANCODE.write();
-    *scan = code;
-    drop(scan);
+    fn draw(&self, vga: &mut Mode13hDisplay, zoom: usize) {
+        let y = 200 - self.font.height as isize;
+        vga.fill_rect(0, y, 320, self.font.height, self.bg);
+        let x = self.font.draw_str(vga, 0, y, &self.text, self.fg, None);
+        let zoom = format!("  zoom {}x", zoom);
+        self.font.draw_str(vga, x, y, &zoom, self.fg, None);
+    }
+}
 
-    unsafe { asm!(
-        "mov ds, bx",
-        "out 0x20, al",
-        in("al") 0x20_u8,
-        in("bx") old_ds
-    ); }
+struct Position {
+    x: isize,
+    y: isize,
 }
@@ -0,0 +1,405 @@
+#![allow(dead_code)]
+use core::arch::{asm, global_asm};
+use core::sync::atomic::{AtomicU32, AtomicU8, AtomicUsize, Ordering};
+
+use crate::dpmi::{self, DosMemory, DpmiRegs};
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Buttons(pub u8);
+
+impl Buttons {
+    pub const LEFT: u8 = 0x1;
+    pub const RIGHT: u8 = 0x2;
+    pub const MIDDLE: u8 = 0x4;
+
+    pub fn contains(self, buttons: u8) -> bool { self.0 & buttons != 0 }
+    pub fn left(self) -> bool { self.contains(Self::LEFT) }
+    pub fn right(self) -> bool { self.contains(Self::RIGHT) }
+    pub fn middle(self) -> bool { self.contains(Self::MIDDLE) }
+}
+
+// The conditions an event handler gets called for, INT 33h AX=0Ch's call mask
+pub struct EventMask;
+
+impl EventMask {
+    pub const MOVED: u16 = 0x01;
+    pub const LEFT_DOWN: u16 = 0x02;
+    pub const LEFT_UP: u16 = 0x04;
+    pub const RIGHT_DOWN: u16 = 0x08;
+    pub const RIGHT_UP: u16 = 0x10;
+    pub const MIDDLE_DOWN: u16 = 0x20;
+    pub const MIDDLE_UP: u16 = 0x40;
+    // CuteMouse wheel API, only once it's been enabled with AX=11h
+    pub const WHEEL: u16 = 0x80;
+    pub const ALL: u16 = 0xff;
+
+    // press condition for one of the Buttons, release is the next bit up
+    fn pressed(button: u8) -> u16 {
+        match button {
+            Buttons::LEFT => Self::LEFT_DOWN,
+            Buttons::RIGHT => Self::RIGHT_DOWN,
+            Buttons::MIDDLE => Self::MIDDLE_DOWN,
+            _ => 0,
+        }
+    }
+}
+
+// Positions are in the driver's virtual screen coordinates,
+// 640x200 in both mode 3 and mode 13h
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct MouseEvent {
+    pub x: u16,
+    pub y: u16,
+    // button state after this event
+    pub buttons: Buttons,
+    // which EventMask conditions caused this event
+    pub conditions: u16,
+    // wheel movement, positive is towards the user
+    pub wheel: i8,
+}
+
+impl MouseEvent {
+    pub fn moved(&self) -> bool { self.conditions & EventMask::MOVED != 0 }
+    pub fn pressed(&self, button: u8) -> bool { self.conditions & EventMask::pressed(button) != 0 }
+    pub fn released(&self, button: u8) -> bool { self.conditions & (EventMask::pressed(button) << 1) != 0 }
+
+    // packed as x | y << 16 and conditions | buttons << 8 | wheel << 16, for the queue
+    fn pack(self) -> (u32, u32) {
+        (self.x as u32 | (self.y as u32) << 16,
+         (self.conditions & 0xff) as u32 | (self.buttons.0 as u32) << 8 | (self.wheel as u8 as u32) << 16)
+    }
+
+    fn unpack(position: u32, state: u32) -> Self {
+        Self {
+            x: position as u16,
+            y: (position >> 16) as u16,
+            buttons: Buttons((state >> 8) as u8),
+            conditions: (state & 0xff) as u16,
+            wheel: (state >> 16) as u8 as i8,
+        }
+    }
+}
+
+const QUEUE_LEN: usize = 64;
+// ring buffer of packed MouseEvents, same scheme as the keyboard queue:
+// the callback only writes at QUEUE_HEAD and readers only move QUEUE_TAIL
+static QUEUE_POSITION: [AtomicU32; QUEUE_LEN] = [const { AtomicU32::new(0) }; QUEUE_LEN];
+static QUEUE_STATE: [AtomicU32; QUEUE_LEN] = [const { AtomicU32::new(0) }; QUEUE_LEN];
+static QUEUE_HEAD: AtomicUsize = AtomicUsize::new(0);
+static QUEUE_TAIL: AtomicUsize = AtomicUsize::new(0);
+
+// latest state the callback saw, so it can be polled without going to real mode
+static POSITION: AtomicU32 = AtomicU32::new(0);
+static BUTTONS: AtomicU8 = AtomicU8::new(0);
+
+// Takes the oldest event out of the queue
+pub fn read_event() -> Option<MouseEvent> {
+    let tail = QUEUE_TAIL.load(Ordering::Relaxed);
+    if tail == QUEUE_HEAD.load(Ordering::Acquire) {
+        return None;
+    }
+    let position = QUEUE_POSITION[tail].load(Ordering::Relaxed);
+    let state = QUEUE_STATE[tail].load(Ordering::Relaxed);
+    QUEUE_TAIL.store((tail + 1) % QUEUE_LEN, Ordering::Release);
+    Some(MouseEvent::unpack(position, state))
+}
+
+pub fn clear_events() {
+    QUEUE_TAIL.store(QUEUE_HEAD.load(Ordering::Acquire), Ordering::Release);
+}
+
+// Position and buttons as of the last event the handler got
+pub fn last_state() -> (u16, u16, Buttons) {
+    let position = POSITION.load(Ordering::Relaxed);
+    (position as u16, (position >> 16) as u16, Buttons(BUTTONS.load(Ordering::Relaxed)))
+}
+
+fn push_event(event: MouseEvent) {
+    let (position, state) = event.pack();
+    POSITION.store(position, Ordering::Relaxed);
+    BUTTONS.store(event.buttons.0, Ordering::Relaxed);
+
+    let head = QUEUE_HEAD.load(Ordering::Relaxed);
+    let next = (head + 1) % QUEUE_LEN;
+    // drop the event if the queue is full
+    if next == QUEUE_TAIL.load(Ordering::Acquire) { return }
+    QUEUE_POSITION[head].store(position, Ordering::Relaxed);
+    QUEUE_STATE[head].store(state, Ordering::Relaxed);
+    QUEUE_HEAD.store(next, Ordering::Release);
+}
+
+// Called by mouse_callback_entry on our own stack, with the registers the driver called us with
+// In:
+//     AX = condition mask
+//     BL = button state
+//     BH = wheel movement (CuteMouse)
+//     CX = x
+//     DX = y
+extern "C" fn mouse_callback(regs: &DpmiRegs) {
+    let (eax, ebx, ecx, edx) = (regs.eax, regs.ebx, regs.ecx, regs.edx);
+    let conditions = eax as u16;
+    push_event(MouseEvent {
+        x: ecx as u16,
+        y: edx as u16,
+        buttons: Buttons(ebx as u8 & 0x7),
+        conditions,
+        wheel: if conditions & EventMask::WHEEL != 0 { (ebx >> 8) as u8 as i8 } else { 0 },
+    });
+}
+
+// the real mode registers for the callback, the DPMI host fills these in
+static mut CALLBACK_REGS: DpmiRegs = DpmiRegs::zero();
+// the host calls us on its own locked stack with SS != DS, so the callback switches to this one
+const CALLBACK_STACK_SIZE: usize = 4096;
+static mut CALLBACK_STACK: [u8; CALLBACK_STACK_SIZE] = [0; CALLBACK_STACK_SIZE];
+// host's ESP and SS while we're on CALLBACK_STACK
+static mut CALLBACK_SAVED_STACK: [u32; 2] = [0; 2];
+
+extern "C" {
+    fn mouse_callback_entry();
+}
+
+// DPMI real mode callback entry point
+// In:
+//     DS:ESI = real mode SS:SP
+//     ES:EDI = real mode call structure (CALLBACK_REGS, so ES is our data selector)
+//     interrupts disabled
+// The driver far calls the callback, so we have to do the RETF ourselves by
+// popping CS:IP off the real mode stack into the call structure's IP/CS (0x2A/0x2C)
+global_asm!(
+    ".global mouse_callback_entry",
+    "mouse_callback_entry:",
+    "cld",
+    "lodsd",
+    "mov es:[edi + 0x2a], eax",
+    "add word ptr es:[edi + 0x2e], 4",
+    "mov ax, es",
+    "mov ds, ax",
+    "mov [{saved}], esp",
+    "mov word ptr [{saved} + 4], ss",
+    "mov ss, ax",
+    "mov esp, offset {stack} + {stack_size}",
+    "push edi",
+    "call {callback}",
+    "pop edi",
+    "lss esp, fword ptr [{saved}]",
+    "iretd",
+    saved = sym CALLBACK_SAVED_STACK,
+    stack = sym CALLBACK_STACK,
+    stack_size = const CALLBACK_STACK_SIZE,
+    callback = sym mouse_callback,
+);
+
+fn mouse_int(regs: &mut DpmiRegs) {
+    dpmi::real_int(0x33, regs);
+}
+
+// The mouse driver, reset while this exists and again on drop
+pub struct Mouse {
+    pub buttons: u8,
+    // the driver has the CuteMouse wheel API, and it's enabled
+    pub wheel: bool,
+    // real mode segment:offset of our callback while it's installed
+    callback: Option<(u16, u16)>,
+    // for passing cursor shapes to the driver
+    cursor_memory: Option<DosMemory>,
+}
+
+impl Mouse {
+    // Resets the driver for the current video mode, None if there's no mouse
+    pub fn detect() -> Option<Self> {
+        // INT 33,0 Reset driver and read status
+        // Out:
+        //     AX = FFFFh if a driver is installed, 0 if not
+        //     BX = number of buttons
+        let mut regs = DpmiRegs::zero();
+        mouse_int(&mut regs);
+        if regs.eax as u16 != 0xffff { return None }
+        let buttons = match regs.ebx as u16 {
+            // 0xffff is 2 buttons, 3 is a Mouse Systems mouse
+            0xffff => 2,
+            n => n as u8,
+        };
+
+        // INT 33,11 CuteMouse: Check wheel support and get capabilities flags
+        // Out:
+        //     AX = 574Dh ('WM') if the wheel API is supported
+        //     CX bit 0 set if a wheel is present
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x11;
+        mouse_int(&mut regs);
+        let wheel = regs.eax as u16 == 0x574d && regs.ecx & 1 != 0;
+
+        Some(Self { buttons, wheel, callback: None, cursor_memory: None })
+    }
+
+    // INT 33,1 Show mouse cursor
+    pub fn show_cursor(&self) {
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x1;
+        mouse_int(&mut regs);
+    }
+
+    // INT 33,2 Hide mouse cursor, calls nest so each one needs a show
+    pub fn hide_cursor(&self) {
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x2;
+        mouse_int(&mut regs);
+    }
+
+    // INT 33,3 Get position and button status
+    // Out:
+    //     BX = button status
+    //     CX = x
+    //     DX = y
+    pub fn state(&self) -> (u16, u16, Buttons) {
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x3;
+        mouse_int(&mut regs);
+        (regs.ecx as u16, regs.edx as u16, Buttons(regs.ebx as u8 & 0x7))
+    }
+
+    // INT 33,4 Set mouse cursor position
+    pub fn set_position(&self, x: u16, y: u16) {
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x4;
+        regs.ecx = x as u32;
+        regs.edx = y as u32;
+        mouse_int(&mut regs);
+    }
+
+    // INT 33,7 and INT 33,8 Set horizontal and vertical limits
+    pub fn set_range(&self, x_min: u16, x_max: u16, y_min: u16, y_max: u16) {
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x7;
+        regs.ecx = x_min as u32;
+        regs.edx = x_max as u32;
+        mouse_int(&mut regs);
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x8;
+        regs.ecx = y_min as u32;
+        regs.edx = y_max as u32;
+        mouse_int(&mut regs);
+    }
+
+    // INT 33,A Set text cursor
+    // The cell under the cursor is ANDed with screen_mask then XORed with cursor_mask,
+    // both are attribute << 8 | character
+    pub fn set_text_cursor(&self, screen_mask: u16, cursor_mask: u16) {
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0xa;
+        regs.ebx = 0;
+        regs.ecx = screen_mask as u32;
+        regs.edx = cursor_mask as u32;
+        mouse_int(&mut regs);
+    }
+
+    // INT 33,9 Set graphics cursor
+    // In:
+    //     BX, CX = hot spot
+    //     ES:DX = 16 words of screen mask, then 16 words of cursor mask
+    pub fn set_graphics_cursor(&mut self, hot_x: i16, hot_y: i16, screen_mask: &[u16; 16], cursor_mask: &[u16; 16]) -> bool {
+        if self.cursor_memory.is_none() {
+            self.cursor_memory = DosMemory::alloc(64);
+        }
+        let memory = match &mut self.cursor_memory {
+            Some(memory) => memory,
+            None => return false,
+        };
+        let words = screen_mask.iter().chain(cursor_mask.iter());
+        for (bytes, word) in memory.as_mut_slice().chunks_exact_mut(2).zip(words) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0x9;
+        regs.ebx = hot_x as u16 as u32;
+        regs.ecx = hot_y as u16 as u32;
+        regs.es = memory.segment;
+        regs.edx = 0;
+        mouse_int(&mut regs);
+        true
+    }
+
+    // Has the driver call us for the conditions in mask (EventMask bits), events go to the queue
+    pub fn install_handler(&mut self, mask: u16) -> bool {
+        self.remove_handler();
+        clear_events();
+
+        // DPMI function 0303h - Allocate Real Mode Callback Address
+        // In:
+        //     DS:ESI = selector:offset of procedure to call
+        //     ES:EDI = selector:offset of real mode call structure
+        // Out:
+        //     if successful:
+        //     CF clear
+        //     CX:DX = segment:offset of real mode call address
+        //
+        //     if failed:
+        //     CF set
+        // ESI can't be given to asm! directly, so it goes in through EDX
+        let err: u8;
+        let segment: u16;
+        let offset: u32;
+        unsafe { asm!(
+            "push esi",
+            "push ds",
+            "mov esi, edx",
+            "mov dx, cs",
+            "mov ds, dx",
+            "int 0x31",
+            "pop ds",
+            "pop esi",
+            "setc bl",
+            inout("eax") 0x0303_u32 => _,
+            inout("edx") mouse_callback_entry as *const () as u32 => offset,
+            in("edi") &raw mut CALLBACK_REGS,
+            out("cx") segment,
+            out("bl") err,
+        );}
+        if err == 1 { return false }
+        self.callback = Some((segment, offset as u16));
+
+        // INT 33,C Set user defined subroutine input mask
+        // In:
+        //     CX = call mask
+        //     ES:DX = far pointer to the subroutine
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0xc;
+        regs.ecx = mask as u32;
+        regs.es = segment;
+        regs.edx = offset & 0xffff;
+        mouse_int(&mut regs);
+        true
+    }
+
+    pub fn remove_handler(&mut self) {
+        let (segment, offset) = match self.callback.take() {
+            Some(callback) => callback,
+            None => return,
+        };
+        let mut regs = DpmiRegs::zero();
+        regs.eax = 0xc;
+        regs.ecx = 0;
+        mouse_int(&mut regs);
+
+        // DPMI function 0304h - Free Real Mode Callback Address
+        // In:
+        //     CX:DX = real mode callback address
+        unsafe { asm!(
+            "int 0x31",
+            inout("ax") 0x0304_u16 => _,
+            in("cx") segment,
+            in("dx") offset,
+        );}
+    }
+}
+
+impl Drop for Mouse {
+    fn drop(&mut self) {
+        self.remove_handler();
+        // resetting hides the cursor and puts the driver back to its defaults
+        let mut regs = DpmiRegs::zero();
+        mouse_int(&mut regs);
+    }
+}
@@ -0,0 +1,155 @@
+#![allow(dead_code)]
+use alloc::vec::Vec;
+
+use crate::vga::{self, Vga18};
+
+pub const BLACK: Vga18 = Vga18 { red: 0, green: 0, blue: 0 };
+pub const WHITE: Vga18 = Vga18 { red: 63, green: 63, blue: 63 };
+
+// Reads back the whole DAC, so it can be restored later
+pub fn save() -> Vec<Vga18> {
+    let mut palette = alloc::vec![Vga18::default(); 256];
+    vga::get_vga_dac_colors(0, &mut palette);
+    palette
+}
+
+pub fn restore(palette: &[Vga18]) {
+    vga::wait_vertical_retrace();
+    vga::set_vga_dac_colors(0, palette);
+}
+
+fn lerp(from: u8, to: u8, step: u32, steps: u32) -> u8 {
+    let (from, to) = (from as i32, to as i32);
+    (from + (to - from) * step as i32 / steps as i32) as u8
+}
+
+// Writes the palette `step / steps` of the way from `from` to `to` into `out`
+pub fn blend(from: &[Vga18], to: &[Vga18], step: u32, steps: u32, out: &mut [Vga18]) {
+    for ((out, a), b) in out.iter_mut().zip(from).zip(to) {
+        *out = Vga18 {
+            red: lerp(a.red, b.red, step, steps),
+            green: lerp(a.green, b.green, step, steps),
+            blue: lerp(a.blue, b.blue, step, steps),
+        };
+    }
+}
+
+// Fades the DAC from one palette to another over `frames` vertical retraces
+pub fn cross_fade(start_index: u8, from: &[Vga18], to: &[Vga18], frames: u32) {
+    let mut current = from.to_vec();
+    for step in 1..=frames.max(1) {
+        blend(from, to, step, frames.max(1), &mut current);
+        vga::wait_vertical_retrace();
+        vga::set_vga_dac_colors(start_index, &current);
+    }
+}
+
+pub fn fade_to_color(start_index: u8, from: &[Vga18], color: Vga18, frames: u32) {
+    let to = alloc::vec![color; from.len()];
+    cross_fade(start_index, from, &to, frames);
+}
+
+pub fn fade_from_color(start_index: u8, to: &[Vga18], color: Vga18, frames: u32) {
+    let from = alloc::vec![color; to.len()];
+    cross_fade(start_index, &from, to, frames);
+}
+
+pub fn fade_to_black(start_index: u8, from: &[Vga18], frames: u32) {
+    fade_to_color(start_index, from, BLACK, frames);
+}
+
+pub fn fade_from_black(start_index: u8, to: &[Vga18], frames: u32) {
+    fade_from_color(start_index, to, BLACK, frames);
+}
+
+pub fn fade_to_white(start_index: u8, from: &[Vga18], frames: u32) {
+    fade_to_color(start_index, from, WHITE, frames);
+}
+
+pub fn fade_from_white(start_index: u8, to: &[Vga18], frames: u32) {
+    fade_from_color(start_index, to, WHITE, frames);
+}
+
+// A color cycling range, same meaning as a Deluxe Paint CRNG chunk:
+// a rate of 16384 steps the range once per 1/60th of a second
+#[derive(Copy, Clone, Default)]
+pub struct CycleRange {
+    pub low: u8,
+    pub high: u8,
+    pub rate: u16,
+    pub active: bool,
+    pub reverse: bool,
+}
+
+const CRNG_RATE_ONE_STEP: u32 = 16384;
+const CRNG_FLAG_ACTIVE: u16 = 0x01;
+const CRNG_FLAG_REVERSE: u16 = 0x02;
+
+impl CycleRange {
+    pub fn new(low: u8, high: u8, rate: u16) -> Self {
+        Self { low, high, rate, active: true, reverse: false }
+    }
+
+    // Parses the 8 byte body of an IFF CRNG chunk
+    // WORD pad, WORD rate, WORD flags, UBYTE low, UBYTE high (all big endian)
+    pub fn from_crng(chunk: &[u8]) -> Option<Self> {
+        if chunk.len() < 8 { return None }
+        let rate = u16::from_be_bytes([chunk[2], chunk[3]]);
+        let flags = u16::from_be_bytes([chunk[4], chunk[5]]);
+        Some(Self {
+            low: chunk[6],
+            high: chunk[7],
+            rate,
+            active: flags & CRNG_FLAG_ACTIVE != 0 && rate != 0,
+            reverse: flags & CRNG_FLAG_REVERSE != 0,
+        })
+    }
+}
+
+// Rotates ranges of a palette and pushes the result to the DAC
+pub struct ColorCycler {
+    palette: Vec<Vga18>,
+    ranges: Vec<(CycleRange, u32)>,
+}
+
+impl ColorCycler {
+    pub fn new(palette: &[Vga18], ranges: &[CycleRange]) -> Self {
+        Self {
+            palette: palette.to_vec(),
+            ranges: ranges.iter().map(|&r| (r, 0)).collect(),
+        }
+    }
+
+    pub fn palette(&self) -> &[Vga18] { &self.palette }
+
+    // Advances all ranges by `frames` 60ths of a second,
+    // returns true if any color moved
+    pub fn advance(&mut self, frames: u32) -> bool {
+        let mut changed = false;
+        for (range, acc) in self.ranges.iter_mut() {
+            let (low, high) = (range.low as usize, range.high as usize);
+            if !range.active || low >= high || high >= self.palette.len() { continue }
+
+            *acc += range.rate as u32 * frames;
+            let steps = *acc / CRNG_RATE_ONE_STEP;
+            *acc %= CRNG_RATE_ONE_STEP;
+            if steps == 0 { continue }
+
+            let colors = &mut self.palette[low..=high];
+            let steps = steps as usize % colors.len();
+            if range.reverse {
+                colors.rotate_left(steps);
+            } else {
+                colors.rotate_right(steps);
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    // Waits for the retrace and updates the DAC with the current palette
+    pub fn apply(&self) {
+        vga::wait_vertical_retrace();
+        vga::set_vga_dac_colors(0, &self.palette);
+    }
+}
@@ -2,7 +2,7 @@ use crate::dpmi::dpmi_exit;
 
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
-    crate::println!("Panic! {}", info);
+    crate::eprintln!("Panic! {}", info);
     dpmi_exit();
 }
 
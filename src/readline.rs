@@ -0,0 +1,227 @@
+#![allow(dead_code)]
+use alloc::{string::String, vec::Vec};
+
+use crate::codepage;
+use crate::dpmi::{self, ShiftState};
+use crate::io::{self, BufRead, BufReader, Stdin};
+use crate::print;
+
+// scan codes of the extended keys used for editing
+const HOME: u8 = 0x47;
+const UP: u8 = 0x48;
+const LEFT: u8 = 0x4B;
+const RIGHT: u8 = 0x4D;
+const END: u8 = 0x4F;
+const DOWN: u8 = 0x50;
+const INSERT: u8 = 0x52;
+const DELETE: u8 = 0x53;
+const CTRL_LEFT: u8 = 0x73;
+const CTRL_RIGHT: u8 = 0x74;
+
+const CTRL_C: u8 = 0x03;
+const BACKSPACE: u8 = 0x08;
+const ENTER: u8 = 0x0D;
+const ESCAPE: u8 = 0x1B;
+// the end of file marker in DOS text files
+const CTRL_Z: u8 = 0x1A;
+
+// Reads lines from the keyboard with editing and history, or straight from stdin when it's redirected
+//
+// Left, Right, Home, End and Ctrl+Left/Right move around, Backspace and Delete delete,
+// Insert switches to overwriting, Up and Down go through the history, Escape clears the line
+// The line is edited in place after whatever prompt was printed, so it should fit on the screen
+pub struct LineEditor {
+    history: Vec<String>,
+    pub max_history: usize,
+    // stdin when it's a file or a pipe, kept so the buffered bytes aren't lost between lines
+    piped: Option<BufReader<Stdin>>,
+    // a Ctrl+Z turned up in piped input, so there are no more lines
+    eof: bool,
+}
+
+// The line being edited, with the screen cursor kept at cursor
+struct Edit {
+    line: Vec<char>,
+    cursor: usize,
+}
+
+impl Edit {
+    // Moves the screen cursor, backspaces go left and reprinting goes right
+    fn move_to(&mut self, pos: usize) {
+        let pos = pos.min(self.line.len());
+        if pos < self.cursor {
+            for _ in pos..self.cursor { print!("\x08"); }
+        } else {
+            for &c in &self.line[self.cursor..pos] { print!("{}", c); }
+        }
+        self.cursor = pos;
+    }
+
+    // Redraws from `from` to the end after a change, with the screen cursor at `from`,
+    // blanking what's left of a line that used to be old_len long
+    fn redraw(&mut self, from: usize, old_len: usize) {
+        for &c in &self.line[from..] { print!("{}", c); }
+        let blanks = old_len.saturating_sub(self.line.len());
+        for _ in 0..blanks { print!(" "); }
+        for _ in self.cursor..self.line.len() + blanks { print!("\x08"); }
+    }
+
+    fn replace(&mut self, text: &[char]) {
+        self.move_to(0);
+        let old_len = self.line.len();
+        self.line = text.to_vec();
+        self.cursor = self.line.len();
+        self.redraw(0, old_len);
+    }
+
+    fn insert(&mut self, c: char, max_len: usize, overwrite: bool) {
+        let old_len = self.line.len();
+        let at = self.cursor;
+        if overwrite && at < old_len {
+            self.line[at] = c;
+        } else if old_len < max_len {
+            self.line.insert(at, c);
+        } else {
+            return;
+        }
+        self.cursor += 1;
+        self.redraw(at, old_len);
+    }
+
+    fn delete(&mut self) {
+        if self.cursor >= self.line.len() { return }
+        let old_len = self.line.len();
+        self.line.remove(self.cursor);
+        self.redraw(self.cursor, old_len);
+    }
+
+    // the start of the word before the cursor
+    fn word_left(&self) -> usize {
+        let mut pos = self.cursor;
+        while pos > 0 && self.line[pos - 1] == ' ' { pos -= 1; }
+        while pos > 0 && self.line[pos - 1] != ' ' { pos -= 1; }
+        pos
+    }
+
+    // the start of the word after the cursor
+    fn word_right(&self) -> usize {
+        let mut pos = self.cursor;
+        while pos < self.line.len() && self.line[pos] != ' ' { pos += 1; }
+        while pos < self.line.len() && self.line[pos] == ' ' { pos += 1; }
+        pos
+    }
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self { history: Vec::new(), max_history: 32, piped: None, eof: false }
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    // Reads a line of at most max_len characters, without the line ending
+    // None at the end of redirected input, or when Ctrl+C is pressed
+    pub fn read_line(&mut self, max_len: usize) -> Option<String> {
+        if !dpmi::is_console_input(dpmi::STDIN) {
+            return self.read_piped(max_len);
+        }
+
+        let page = codepage::active();
+        let mut edit = Edit { line: Vec::new(), cursor: 0 };
+        // starts out however the BIOS Insert toggle is, Insert on means inserting
+        let mut overwrite = !dpmi::shift_state().contains(ShiftState::INSERT);
+        // where we are in the history, history.len() is the line being typed
+        let mut browsing = self.history.len();
+        let mut typed: Vec<char> = Vec::new();
+
+        loop {
+            let key = dpmi::read_key();
+            if key.is_extended() {
+                match key.scan {
+                    LEFT => edit.move_to(edit.cursor.saturating_sub(1)),
+                    RIGHT => edit.move_to(edit.cursor + 1),
+                    HOME => edit.move_to(0),
+                    END => edit.move_to(edit.line.len()),
+                    CTRL_LEFT => edit.move_to(edit.word_left()),
+                    CTRL_RIGHT => edit.move_to(edit.word_right()),
+                    DELETE => edit.delete(),
+                    INSERT => overwrite = !overwrite,
+                    UP if browsing > 0 => {
+                        if browsing == self.history.len() { typed = edit.line.clone(); }
+                        browsing -= 1;
+                        let entry: Vec<char> = self.history[browsing].chars().take(max_len).collect();
+                        edit.replace(&entry);
+                    }
+                    DOWN if browsing < self.history.len() => {
+                        browsing += 1;
+                        match self.history.get(browsing) {
+                            Some(entry) => {
+                                let entry: Vec<char> = entry.chars().take(max_len).collect();
+                                edit.replace(&entry);
+                            }
+                            None => edit.replace(&typed),
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            match key.ascii {
+                ENTER => break,
+                CTRL_C => {
+                    edit.move_to(edit.line.len());
+                    print!("^C\r\n");
+                    return None;
+                }
+                ESCAPE => {
+                    edit.replace(&[]);
+                    browsing = self.history.len();
+                }
+                BACKSPACE if edit.cursor > 0 => {
+                    edit.move_to(edit.cursor - 1);
+                    edit.delete();
+                }
+                // the rest of the control characters don't go in the line
+                byte if byte >= 0x20 => edit.insert(page.decode_byte(byte), max_len, overwrite),
+                _ => {}
+            }
+        }
+
+        edit.move_to(edit.line.len());
+        print!("\r\n");
+        let line: String = edit.line.into_iter().collect();
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > self.max_history {
+                self.history.remove(0);
+            }
+        }
+        Some(line)
+    }
+
+    // A line from a file or pipe, no echo or editing
+    fn read_piped(&mut self, max_len: usize) -> Option<String> {
+        if self.eof { return None }
+        let reader = self.piped.get_or_insert_with(|| BufReader::new(io::stdin()));
+        let mut bytes = Vec::new();
+        let read = reader.read_until(b'\n', &mut bytes).ok()?;
+        if read == 0 { return None }
+        // Ctrl+Z ends a text file, anything after it doesn't count
+        if let Some(end) = bytes.iter().position(|&b| b == CTRL_Z) {
+            self.eof = true;
+            if end == 0 { return None }
+            bytes.truncate(end);
+        }
+        while bytes.last().is_some_and(|&b| b == b'\n' || b == b'\r') {
+            bytes.pop();
+        }
+        Some(codepage::decode(&bytes).chars().take(max_len).collect())
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self { Self::new() }
+}
@@ -0,0 +1,115 @@
+#![allow(dead_code)]
+use core::arch::asm;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::dpmi::IntHandler;
+use crate::vga::outb;
+
+// The PIT runs at 1.193182MHz, the BIOS default divisor of 65536 gives 18.2Hz
+pub const PIT_FREQUENCY: u32 = 1_193_182;
+// 4 times the BIOS rate, so we can keep the BIOS clock exact
+const TICK_DIVISOR: u32 = 16384;
+const BIOS_TICK_EVERY: u32 = 65536 / TICK_DIVISOR;
+pub const TICKS_PER_SECOND: u32 = PIT_FREQUENCY / TICK_DIVISOR;
+
+static TICKS: AtomicU32 = AtomicU32::new(0);
+// what channel 0 is currently counting down from
+static PIT_DIVISOR: AtomicU32 = AtomicU32::new(65536);
+
+pub fn ticks() -> u32 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+pub fn pit_divisor() -> u32 {
+    PIT_DIVISOR.load(Ordering::Relaxed)
+}
+
+// Halts until the next timer tick
+pub fn wait_tick() {
+    let start = ticks();
+    while ticks() == start {
+        unsafe { asm!("hlt"); }
+    }
+}
+
+pub fn wait_ticks(count: u32) {
+    for _ in 0..count {
+        wait_tick();
+    }
+}
+
+fn set_pit_divisor(divisor: u32) {
+    // channel 0, lobyte/hibyte, mode 3 (square wave), a divisor of 0 means 65536
+    unsafe {
+        outb(0x43, 0x36);
+        outb(0x40, divisor as u8);
+        outb(0x40, (divisor >> 8) as u8);
+    }
+    PIT_DIVISOR.store(divisor, Ordering::Relaxed);
+}
+
+// Does the BIOS's INT 8 work, since we don't chain to it
+fn bios_tick() {
+    unsafe {
+        // ticks since midnight at 0x46C, rolls over after 24 hours into the flag at 0x470
+        let count = 0x46c as *mut u32;
+        *count += 1;
+        if *count >= 0x1800b0 {
+            *count = 0;
+            *(0x470 as *mut u8) = 1;
+        }
+        // floppy motor shutoff countdown at 0x440
+        let motor_count = 0x440 as *mut u8;
+        if *motor_count > 0 {
+            *motor_count -= 1;
+            if *motor_count == 0 {
+                *(0x43f as *mut u8) &= 0xf0;
+                outb(0x3f2, 0x0c);
+            }
+        }
+    }
+}
+
+pub extern "x86-interrupt" fn timer_int_handler() {
+    let old_ds: u16;
+    unsafe { asm!(
+        "mov bx, ds",
+        "mov ax, es",
+        "mov ds, ax",
+        out("bx") old_ds
+    ); }
+
+    let ticks = TICKS.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+    if ticks.is_multiple_of(BIOS_TICK_EVERY) {
+        bios_tick();
+    }
+
+    unsafe { asm!(
+        "mov ds, bx",
+        "out 0x20, al",
+        in("al") 0x20_u8,
+        in("bx") old_ds
+    ); }
+}
+
+// Owns the IRQ0 handler and speeds the PIT up to TICKS_PER_SECOND,
+// both are put back on drop
+pub struct Timer {
+    handler: IntHandler,
+}
+
+impl Timer {
+    pub fn install() -> Self {
+        let mut handler = IntHandler::new(8);
+        handler.set_handler(timer_int_handler);
+        set_pit_divisor(TICK_DIVISOR);
+        Self { handler }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        set_pit_divisor(65536);
+        self.handler.restore_handler();
+    }
+}
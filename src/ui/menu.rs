@@ -0,0 +1,209 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::console::{to_cp437, Attribute, Console, Rect};
+
+use super::{contains, draw_frame, frame_area, Event, EventSource, Key, SavedScreen, Theme};
+
+// a separator line in a menu
+const SEPARATOR: &str = "-";
+// CP437 single horizontal line
+const SEPARATOR_CHAR: u8 = 0xc4;
+
+enum PopupResult {
+    Chosen(usize),
+    Cancelled,
+    // Left/Right, for moving along a menu bar
+    Previous,
+    Next,
+    // a click on the menu bar row, at this column
+    Bar(usize),
+}
+
+// draws a label, with the letter after & as the hotkey
+fn put_label(con: &mut Console, x: usize, y: usize, label: &str, theme: &Theme, attr: Attribute) {
+    let mut col = x;
+    let mut hot = false;
+    for c in label.chars() {
+        if c == '&' && !hot {
+            hot = true;
+            continue;
+        }
+        let attr = if hot { theme.hotkey.with_bg(attr.bg()) } else { attr };
+        con.put(col, y, to_cp437(c), attr);
+        hot = false;
+        col += 1;
+    }
+}
+
+fn label_width(label: &str) -> usize {
+    label.chars().filter(|&c| c != '&').count()
+}
+
+fn hotkey(label: &str) -> Option<char> {
+    let mut chars = label.chars();
+    chars.find(|&c| c == '&')?;
+    chars.next().map(|c| c.to_ascii_lowercase())
+}
+
+// A popup menu, items can mark a hotkey with & and "-" is a separator
+pub struct Menu {
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+impl Menu {
+    pub fn new(items: &[&str]) -> Self {
+        let mut menu = Self { items: items.iter().map(|&s| s.into()).collect(), selected: 0 };
+        if menu.items.first().is_some_and(|s| s == SEPARATOR) {
+            menu.step(true);
+        }
+        menu
+    }
+
+    fn rect(&self, x: usize, y: usize) -> Rect {
+        let width = self.items.iter().map(|s| label_width(s)).max().unwrap_or(0);
+        Rect { x, y, width: width + 4, height: self.items.len() + 2 }
+    }
+
+    // moves the selection to the next or previous item, skipping separators
+    fn step(&mut self, forward: bool) {
+        let count = self.items.len();
+        for i in 1..=count {
+            let index = if forward { (self.selected + i) % count } else { (self.selected + count - i) % count };
+            if self.items[index] != SEPARATOR {
+                self.selected = index;
+                return;
+            }
+        }
+    }
+
+    fn draw(&self, con: &mut Console, rect: Rect, theme: &Theme) {
+        draw_frame(con, rect, "", theme);
+        for (i, item) in self.items.iter().enumerate() {
+            let y = rect.y + 1 + i;
+            if item == SEPARATOR {
+                con.fill(Rect { x: rect.x + 1, y, width: rect.width - 2, height: 1 }, SEPARATOR_CHAR, theme.window);
+                continue;
+            }
+            let attr = if i == self.selected { theme.focused } else { theme.window };
+            con.fill(Rect { x: rect.x + 1, y, width: rect.width - 2, height: 1 }, b' ', attr);
+            put_label(con, rect.x + 2, y, item, theme, attr);
+        }
+    }
+
+    fn popup(&mut self, con: &mut Console, events: &mut dyn EventSource, x: usize, y: usize, theme: &Theme) -> PopupResult {
+        if self.items.is_empty() { return PopupResult::Cancelled }
+        let rect = self.rect(x, y);
+        let saved = SavedScreen::save(con, frame_area(rect));
+        con.show_cursor(false);
+
+        let result = loop {
+            self.draw(con, rect, theme);
+            match events.wait_event() {
+                Event::Key(Key::Up) => self.step(false),
+                Event::Key(Key::Down) => self.step(true),
+                Event::Key(Key::Home) => { self.selected = self.items.len() - 1; self.step(true); }
+                Event::Key(Key::End) => { self.selected = 0; self.step(false); }
+                Event::Key(Key::Enter) => break PopupResult::Chosen(self.selected),
+                Event::Key(Key::Escape) => break PopupResult::Cancelled,
+                Event::Key(Key::Left) => break PopupResult::Previous,
+                Event::Key(Key::Right) => break PopupResult::Next,
+                Event::Key(Key::Char(c)) => {
+                    let c = c.to_ascii_lowercase();
+                    if let Some(i) = self.items.iter().position(|s| hotkey(s) == Some(c)) {
+                        self.selected = i;
+                        break PopupResult::Chosen(i);
+                    }
+                }
+                Event::MouseDown { col, row, .. } | Event::MouseMove { col, row } if contains(rect, col, row) => {
+                    let i = row.saturating_sub(rect.y + 1);
+                    if self.items.get(i).is_some_and(|s| s != SEPARATOR) {
+                        self.selected = i;
+                    }
+                }
+                Event::MouseUp { col, row, .. } if contains(rect, col, row) => {
+                    let i = row.saturating_sub(rect.y + 1);
+                    if self.items.get(i).is_some_and(|s| s != SEPARATOR) {
+                        self.selected = i;
+                        break PopupResult::Chosen(i);
+                    }
+                }
+                Event::MouseDown { col, row: 0, .. } => break PopupResult::Bar(col),
+                Event::MouseDown { .. } => break PopupResult::Cancelled,
+                _ => {}
+            }
+        };
+
+        saved.restore(con);
+        result
+    }
+
+    // Pops the menu up at (x, y), returns the chosen item or None if cancelled
+    pub fn run(&mut self, con: &mut Console, events: &mut dyn EventSource, x: usize, y: usize, theme: &Theme) -> Option<usize> {
+        loop {
+            match self.popup(con, events, x, y, theme) {
+                PopupResult::Chosen(i) => return Some(i),
+                PopupResult::Cancelled | PopupResult::Bar(_) => return None,
+                // not on a bar, so just stay open
+                PopupResult::Previous | PopupResult::Next => {}
+            }
+        }
+    }
+}
+
+// A row of pull down menus along the top line of the screen
+pub struct MenuBar {
+    pub menus: Vec<(String, Menu)>,
+    pub selected: usize,
+}
+
+impl MenuBar {
+    pub fn new(menus: Vec<(&str, Menu)>) -> Self {
+        Self { menus: menus.into_iter().map(|(t, m)| (t.into(), m)).collect(), selected: 0 }
+    }
+
+    // column each title starts at
+    fn title_columns(&self) -> impl Iterator<Item = usize> + '_ {
+        self.menus.iter().scan(1, |x, (title, _)| {
+            let col = *x;
+            *x += label_width(title) + 2;
+            Some(col)
+        })
+    }
+
+    pub fn draw(&self, con: &mut Console, theme: &Theme, open: bool) {
+        con.fill(Rect { x: 0, y: 0, width: con.width, height: 1 }, b' ', theme.control);
+        let columns: Vec<usize> = self.title_columns().collect();
+        for (i, ((title, _), col)) in self.menus.iter().zip(columns).enumerate() {
+            let attr = if open && i == self.selected { theme.focused } else { theme.control };
+            con.put_str(col, 0, " ", attr);
+            put_label(con, col + 1, 0, title, theme, attr);
+            con.put_str(col + 1 + label_width(title), 0, " ", attr);
+        }
+    }
+
+    // Opens the selected menu, returns (menu, item) when something is chosen
+    pub fn run(&mut self, con: &mut Console, events: &mut dyn EventSource, theme: &Theme) -> Option<(usize, usize)> {
+        let count = self.menus.len();
+        if count == 0 { return None }
+        let result = loop {
+            self.draw(con, theme, true);
+            let x = self.title_columns().nth(self.selected).unwrap_or(0);
+            match self.menus[self.selected].1.popup(con, events, x, 1, theme) {
+                PopupResult::Chosen(i) => break Some((self.selected, i)),
+                PopupResult::Cancelled => break None,
+                PopupResult::Previous => self.selected = (self.selected + count - 1) % count,
+                PopupResult::Next => self.selected = (self.selected + 1) % count,
+                PopupResult::Bar(col) => {
+                    let columns: Vec<usize> = self.title_columns().collect();
+                    match columns.iter().rposition(|&c| c <= col) {
+                        Some(i) => self.selected = i,
+                        None => break None,
+                    }
+                }
+            }
+        };
+        self.draw(con, theme, false);
+        result
+    }
+}
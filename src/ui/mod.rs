@@ -0,0 +1,529 @@
+#![allow(dead_code)]
+// Text mode UI on top of the direct to VRAM console
+
+use alloc::{string::String, vec::Vec};
+use core::arch::asm;
+
+use crate::console::{Attribute, BoxStyle, Color, Console, Rect};
+use crate::dpmi;
+use crate::keyboard::{self, KeyCode, KeyEvent};
+use crate::mouse::{self, Buttons};
+
+mod menu;
+mod widgets;
+
+#[allow(unused_imports)]
+pub use menu::{Menu, MenuBar};
+#[allow(unused_imports)]
+pub use widgets::{Button, CheckBox, InputField, Label, ListBox};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Escape,
+    Tab,
+    BackTab,
+    Backspace,
+    Delete,
+    Insert,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    F(u8),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+// Mouse positions are in text cells
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Event {
+    Key(Key),
+    MouseDown { col: usize, row: usize, button: MouseButton },
+    MouseUp { col: usize, row: usize, button: MouseButton },
+    MouseMove { col: usize, row: usize },
+}
+
+impl Event {
+    fn mouse_pos(&self) -> Option<(usize, usize)> {
+        match *self {
+            Event::MouseDown { col, row, .. } | Event::MouseUp { col, row, .. } | Event::MouseMove { col, row } => Some((col, row)),
+            Event::Key(_) => None,
+        }
+    }
+
+    // moves mouse positions so they're relative to (x, y)
+    fn relative_to(self, x: usize, y: usize) -> Option<Self> {
+        Some(match self {
+            Event::MouseDown { col, row, button } => Event::MouseDown { col: col.checked_sub(x)?, row: row.checked_sub(y)?, button },
+            Event::MouseUp { col, row, button } => Event::MouseUp { col: col.checked_sub(x)?, row: row.checked_sub(y)?, button },
+            Event::MouseMove { col, row } => Event::MouseMove { col: col.checked_sub(x)?, row: row.checked_sub(y)? },
+            key => key,
+        })
+    }
+}
+
+// Anything that can give the UI input, blocks until there is some
+pub trait EventSource {
+    fn wait_event(&mut self) -> Event;
+}
+
+// Keyboard input through BIOS INT 16h, plus the mouse if its handler is installed
+pub struct BiosKeyboard;
+
+impl EventSource for BiosKeyboard {
+    fn wait_event(&mut self) -> Event {
+        loop {
+            if dpmi::kb_status().is_some() {
+                if let Some(key) = bios_key(dpmi::getchar()) {
+                    return Event::Key(key);
+                }
+                continue;
+            }
+            match mouse_event() {
+                Some(event) => return event,
+                None => unsafe { asm!("hlt"); }
+            }
+        }
+    }
+}
+
+// The next mouse event from the mouse handler's queue, in text cells
+// The driver's text mode coordinates are 8 per cell in both directions
+pub fn mouse_event() -> Option<Event> {
+    loop {
+        let event = mouse::read_event()?;
+        let (col, row) = (event.x as usize / 8, event.y as usize / 8);
+        for (button, ui_button) in [(Buttons::LEFT, MouseButton::Left), (Buttons::RIGHT, MouseButton::Right), (Buttons::MIDDLE, MouseButton::Middle)] {
+            if event.pressed(button) {
+                return Some(Event::MouseDown { col, row, button: ui_button });
+            }
+            if event.released(button) {
+                return Some(Event::MouseUp { col, row, button: ui_button });
+            }
+        }
+        if event.moved() {
+            return Some(Event::MouseMove { col, row });
+        }
+    }
+}
+
+// Keyboard input through our own keyboard driver, which has to be installed,
+// plus the mouse if its handler is installed
+// Characters come from the current keyboard layout
+#[derive(Default)]
+pub struct DriverKeyboard {
+    // second character of a dead key that didn't combine
+    pending: Option<char>,
+}
+
+impl EventSource for DriverKeyboard {
+    fn wait_event(&mut self) -> Event {
+        if let Some(c) = self.pending.take() {
+            return Event::Key(Key::Char(c));
+        }
+        loop {
+            let event = match keyboard::read_event() {
+                Some(event) if event.pressed => event,
+                Some(_) => continue,
+                None => match mouse_event() {
+                    Some(event) => return event,
+                    None => {
+                        unsafe { asm!("hlt"); }
+                        continue;
+                    }
+                },
+            };
+            if let Some(key) = named_key(event) {
+                return Event::Key(key);
+            }
+            let chars = keyboard::translate(event);
+            if let Some(&c) = chars.first() {
+                self.pending = chars.get(1).copied();
+                return Event::Key(Key::Char(c));
+            }
+            // the keypad is cursor keys when it doesn't type digits
+            if let Some(key) = keypad_key(event.key) {
+                return Event::Key(key);
+            }
+        }
+    }
+}
+
+fn named_key(event: KeyEvent) -> Option<Key> {
+    Some(match event.key {
+        KeyCode::ENTER | KeyCode::KP_ENTER => Key::Enter,
+        KeyCode::ESCAPE => Key::Escape,
+        KeyCode::TAB if event.modifiers.shift() => Key::BackTab,
+        KeyCode::TAB => Key::Tab,
+        KeyCode::BACKSPACE => Key::Backspace,
+        KeyCode::UP => Key::Up,
+        KeyCode::DOWN => Key::Down,
+        KeyCode::LEFT => Key::Left,
+        KeyCode::RIGHT => Key::Right,
+        KeyCode::HOME => Key::Home,
+        KeyCode::END => Key::End,
+        KeyCode::PAGE_UP => Key::PageUp,
+        KeyCode::PAGE_DOWN => Key::PageDown,
+        KeyCode::INSERT => Key::Insert,
+        KeyCode::DELETE => Key::Delete,
+        k if (KeyCode::F1..=KeyCode::F10).contains(&k) => Key::F(k.0 - KeyCode::F1.0 + 1),
+        KeyCode::F11 => Key::F(11),
+        KeyCode::F12 => Key::F(12),
+        _ => return None,
+    })
+}
+
+fn keypad_key(key: KeyCode) -> Option<Key> {
+    Some(match key {
+        KeyCode::KP_8 => Key::Up,
+        KeyCode::KP_2 => Key::Down,
+        KeyCode::KP_4 => Key::Left,
+        KeyCode::KP_6 => Key::Right,
+        KeyCode::KP_7 => Key::Home,
+        KeyCode::KP_1 => Key::End,
+        KeyCode::KP_9 => Key::PageUp,
+        KeyCode::KP_3 => Key::PageDown,
+        KeyCode::KP_0 => Key::Insert,
+        KeyCode::KP_PERIOD => Key::Delete,
+        _ => return None,
+    })
+}
+
+// Translates an INT 16h scan code/ASCII pair
+pub fn bios_key(code: u16) -> Option<Key> {
+    let (scan, ascii) = ((code >> 8) as u8, code as u8);
+    Some(match (scan, ascii) {
+        (_, 0x0d) => Key::Enter,
+        (_, 0x1b) => Key::Escape,
+        (_, 0x09) => Key::Tab,
+        (0x0f, 0) => Key::BackTab,
+        (_, 0x08) => Key::Backspace,
+        // extended keys come with ASCII 0, or E0 for the grey keys
+        (0x48, 0 | 0xe0) => Key::Up,
+        (0x50, 0 | 0xe0) => Key::Down,
+        (0x4b, 0 | 0xe0) => Key::Left,
+        (0x4d, 0 | 0xe0) => Key::Right,
+        (0x47, 0 | 0xe0) => Key::Home,
+        (0x4f, 0 | 0xe0) => Key::End,
+        (0x49, 0 | 0xe0) => Key::PageUp,
+        (0x51, 0 | 0xe0) => Key::PageDown,
+        (0x52, 0 | 0xe0) => Key::Insert,
+        (0x53, 0 | 0xe0) => Key::Delete,
+        (s @ 0x3b..=0x44, 0) => Key::F(s - 0x3a),
+        (_, a @ 0x20..=0x7e) => Key::Char(a as char),
+        _ => return None,
+    })
+}
+
+#[derive(Copy, Clone)]
+pub struct Theme {
+    pub window: Attribute,
+    pub title: Attribute,
+    pub control: Attribute,
+    pub focused: Attribute,
+    pub selected: Attribute,
+    pub hotkey: Attribute,
+    pub shadow: Attribute,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            window: Attribute::new(Color::White, Color::Blue),
+            title: Attribute::new(Color::Yellow, Color::Blue),
+            control: Attribute::new(Color::Black, Color::Cyan),
+            focused: Attribute::new(Color::White, Color::Green),
+            selected: Attribute::new(Color::Black, Color::LightGray),
+            hotkey: Attribute::new(Color::Red, Color::Cyan),
+            shadow: Attribute::new(Color::DarkGray, Color::Black),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Response {
+    // the widget didn't want the event
+    Ignored,
+    Handled,
+    // the widget was pressed, e.g. Enter on a button
+    Activated,
+}
+
+// Widget positions are relative to the inside of the window they're in
+pub trait Widget {
+    fn rect(&self) -> Rect;
+    fn draw(&self, con: &mut Console, x: usize, y: usize, theme: &Theme, focused: bool);
+    fn handle(&mut self, event: &Event) -> Response;
+    fn focusable(&self) -> bool { true }
+    // where the hardware cursor goes while focused, None hides it
+    fn cursor(&self) -> Option<(usize, usize)> { None }
+}
+
+fn contains(rect: Rect, col: usize, row: usize) -> bool {
+    (rect.x..rect.x + rect.width).contains(&col) && (rect.y..rect.y + rect.height).contains(&row)
+}
+
+// Remembers what was on screen under a window, so it can be put back
+pub struct SavedScreen {
+    rect: Rect,
+    cells: Vec<(u8, Attribute)>,
+}
+
+impl SavedScreen {
+    pub fn save(con: &Console, rect: Rect) -> Self {
+        let rect = Rect {
+            width: rect.width.min(con.width.saturating_sub(rect.x)),
+            height: rect.height.min(con.height.saturating_sub(rect.y)),
+            ..rect
+        };
+        let mut cells = Vec::with_capacity(rect.width * rect.height);
+        for y in rect.y..rect.y + rect.height {
+            for x in rect.x..rect.x + rect.width {
+                cells.push(con.get(x, y));
+            }
+        }
+        Self { rect, cells }
+    }
+
+    pub fn restore(&self, con: &mut Console) {
+        let mut cells = self.cells.iter();
+        for y in self.rect.y..self.rect.y + self.rect.height {
+            for x in self.rect.x..self.rect.x + self.rect.width {
+                if let Some(&(ch, attr)) = cells.next() {
+                    con.put(x, y, ch, attr);
+                }
+            }
+        }
+    }
+}
+
+// Draws a framed window with a title and a drop shadow
+pub fn draw_frame(con: &mut Console, rect: Rect, title: &str, theme: &Theme) {
+    con.fill(rect, b' ', theme.window);
+    con.draw_box(rect, BoxStyle::Double, theme.window);
+    if !title.is_empty() {
+        let len = title.chars().count().min(rect.width.saturating_sub(4));
+        let x = rect.x + (rect.width - len) / 2;
+        let title: String = title.chars().take(len).collect();
+        con.put_str(x, rect.y, &title, theme.title);
+    }
+    // shadow, right side and bottom, keeping the characters underneath
+    let shade = |con: &mut Console, x: usize, y: usize| {
+        if x < con.width && y < con.height {
+            let (ch, _) = con.get(x, y);
+            con.put(x, y, ch, theme.shadow);
+        }
+    };
+    for y in rect.y + 1..=rect.y + rect.height {
+        shade(con, rect.x + rect.width, y);
+        shade(con, rect.x + rect.width + 1, y);
+    }
+    for x in rect.x + 2..rect.x + rect.width {
+        shade(con, x, rect.y + rect.height);
+    }
+}
+
+// the area draw_frame touches, shadow included
+fn frame_area(rect: Rect) -> Rect {
+    Rect { width: rect.width + 2, height: rect.height + 1, ..rect }
+}
+
+pub fn centered(con: &Console, width: usize, height: usize) -> Rect {
+    let width = width.min(con.width);
+    let height = height.min(con.height);
+    Rect { x: (con.width - width) / 2, y: (con.height - height) / 2, width, height }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DialogResult {
+    // the widget at this index was activated
+    Activated(usize),
+    Cancelled,
+}
+
+// A window full of widgets, Tab and Shift+Tab move the focus around
+// The widgets stay owned by the caller, so their values can be read afterwards
+pub struct Dialog<'a> {
+    pub rect: Rect,
+    pub title: String,
+    pub theme: Theme,
+    widgets: Vec<&'a mut dyn Widget>,
+    focus: usize,
+}
+
+impl<'a> Dialog<'a> {
+    pub fn new(rect: Rect, title: &str) -> Self {
+        Self { rect, title: title.into(), theme: Theme::default(), widgets: Vec::new(), focus: 0 }
+    }
+
+    // Adds a widget and returns its index
+    pub fn add(&mut self, widget: &'a mut dyn Widget) -> usize {
+        self.widgets.push(widget);
+        self.widgets.len() - 1
+    }
+
+    pub fn focus(&self) -> usize { self.focus }
+
+    pub fn set_focus(&mut self, index: usize) {
+        if self.widgets.get(index).is_some_and(|w| w.focusable()) {
+            self.focus = index;
+        }
+    }
+
+    fn move_focus(&mut self, forward: bool) {
+        let count = self.widgets.len();
+        for i in 1..=count {
+            let next = if forward { (self.focus + i) % count } else { (self.focus + count - i) % count };
+            if self.widgets[next].focusable() {
+                self.focus = next;
+                return;
+            }
+        }
+    }
+
+    // inside of the frame
+    fn origin(&self) -> (usize, usize) { (self.rect.x + 1, self.rect.y + 1) }
+
+    pub fn draw(&self, con: &mut Console) {
+        draw_frame(con, self.rect, &self.title, &self.theme);
+        let (x, y) = self.origin();
+        for (i, widget) in self.widgets.iter().enumerate() {
+            widget.draw(con, x, y, &self.theme, i == self.focus);
+        }
+        match self.widgets.get(self.focus).and_then(|w| w.cursor()) {
+            Some((col, row)) => {
+                con.set_cursor(x + col, y + row);
+                con.show_cursor(true);
+            }
+            None => con.show_cursor(false),
+        }
+    }
+
+    // Runs until a widget is activated or the dialog is cancelled with Escape,
+    // then puts the screen back the way it was
+    pub fn run(&mut self, con: &mut Console, events: &mut dyn EventSource) -> DialogResult {
+        let window = con.window();
+        con.reset_window();
+        let saved = SavedScreen::save(con, frame_area(self.rect));
+        if !self.widgets.get(self.focus).is_some_and(|w| w.focusable()) {
+            self.move_focus(true);
+        }
+
+        let result = loop {
+            self.draw(con);
+            let event = events.wait_event();
+            if let Some(result) = self.dispatch(event) {
+                break result;
+            }
+        };
+
+        saved.restore(con);
+        con.show_cursor(true);
+        con.set_window(window);
+        result
+    }
+
+    fn dispatch(&mut self, event: Event) -> Option<DialogResult> {
+        let (x, y) = self.origin();
+        // clicks outside of the window are ignored
+        let event = event.relative_to(x, y)?;
+
+        // clicks focus whatever is under them
+        if let Event::MouseDown { col, row, .. } = event {
+            self.focus = self.widgets.iter().position(|w| w.focusable() && contains(w.rect(), col, row))?;
+        }
+        // only the focused widget gets mouse events, and only over itself
+        if let Some((col, row)) = event.mouse_pos() {
+            if !self.widgets.get(self.focus).is_some_and(|w| contains(w.rect(), col, row)) {
+                return None;
+            }
+        }
+
+        let response = match self.widgets.get_mut(self.focus) {
+            Some(widget) => widget.handle(&event),
+            None => Response::Ignored,
+        };
+        match (response, event) {
+            (Response::Activated, _) => return Some(DialogResult::Activated(self.focus)),
+            (Response::Handled, _) => {}
+            (Response::Ignored, Event::Key(Key::Escape)) => return Some(DialogResult::Cancelled),
+            (Response::Ignored, Event::Key(Key::Tab | Key::Down | Key::Right)) => self.move_focus(true),
+            (Response::Ignored, Event::Key(Key::BackTab | Key::Up | Key::Left)) => self.move_focus(false),
+            (Response::Ignored, _) => {}
+        }
+        None
+    }
+}
+
+// splits text into lines of at most `width` characters, breaking at spaces where it can
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let (line_len, word_len) = (line.chars().count(), word.chars().count());
+            if line_len > 0 && line_len + 1 + word_len > width {
+                lines.push(core::mem::take(&mut line));
+            }
+            if !line.is_empty() { line.push(' '); }
+            line.push_str(word);
+            // words longer than a line get cut up
+            while line.chars().count() > width {
+                let rest: String = line.chars().skip(width).collect();
+                line = line.chars().take(width).collect();
+                lines.push(core::mem::replace(&mut line, rest));
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+// Shows a message with a row of buttons, returns the index of the button pressed
+// or None if it was cancelled
+pub fn message_box(con: &mut Console, events: &mut dyn EventSource, title: &str, text: &str, buttons: &[&str]) -> Option<usize> {
+    let lines = wrap(text, con.width.saturating_sub(12).min(60));
+    let buttons_width: usize = buttons.iter().map(|b| b.chars().count() + 5).sum();
+    let text_width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let inner_width = text_width.max(buttons_width).max(title.chars().count() + 2);
+    let rect = centered(con, inner_width + 4, lines.len() + 5);
+
+    let mut labels: Vec<Label> = lines.into_iter().enumerate()
+        .map(|(i, line)| Label::new(1, i + 1, &line))
+        .collect();
+    let button_row = labels.len() + 2;
+    let mut button_x = (rect.width - 2).saturating_sub(buttons_width) / 2;
+    let mut button_widgets: Vec<Button> = buttons.iter()
+        .map(|b| {
+            let button = Button::new(button_x, button_row, b);
+            button_x += b.chars().count() + 5;
+            button
+        })
+        .collect();
+
+    let mut dialog = Dialog::new(rect, title);
+    for label in labels.iter_mut() {
+        dialog.add(label);
+    }
+    let first_button = dialog.widgets.len();
+    for button in button_widgets.iter_mut() {
+        dialog.add(button);
+    }
+    dialog.set_focus(first_button);
+
+    match dialog.run(con, events) {
+        DialogResult::Activated(i) => Some(i - first_button),
+        DialogResult::Cancelled => None,
+    }
+}
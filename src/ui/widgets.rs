@@ -0,0 +1,291 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::console::{Console, Rect};
+
+use super::{Event, Key, Response, Theme, Widget};
+
+// characters as the console would show them, one cell each
+fn cells(text: &str) -> usize {
+    text.chars().count()
+}
+
+pub struct Label {
+    pub x: usize,
+    pub y: usize,
+    pub text: String,
+}
+
+impl Label {
+    pub fn new(x: usize, y: usize, text: &str) -> Self {
+        Self { x, y, text: text.into() }
+    }
+}
+
+impl Widget for Label {
+    fn rect(&self) -> Rect {
+        Rect { x: self.x, y: self.y, width: cells(&self.text), height: 1 }
+    }
+
+    fn draw(&self, con: &mut Console, x: usize, y: usize, theme: &Theme, _focused: bool) {
+        con.put_str(x + self.x, y + self.y, &self.text, theme.window);
+    }
+
+    fn handle(&mut self, _event: &Event) -> Response { Response::Ignored }
+
+    fn focusable(&self) -> bool { false }
+}
+
+// Drawn as < label >, activated by Enter, Space or a click
+pub struct Button {
+    pub x: usize,
+    pub y: usize,
+    pub label: String,
+}
+
+impl Button {
+    pub fn new(x: usize, y: usize, label: &str) -> Self {
+        Self { x, y, label: label.into() }
+    }
+}
+
+impl Widget for Button {
+    fn rect(&self) -> Rect {
+        Rect { x: self.x, y: self.y, width: cells(&self.label) + 4, height: 1 }
+    }
+
+    fn draw(&self, con: &mut Console, x: usize, y: usize, theme: &Theme, focused: bool) {
+        let attr = if focused { theme.focused } else { theme.control };
+        let (x, y) = (x + self.x, y + self.y);
+        con.put_str(x, y, "< ", attr);
+        con.put_str(x + 2, y, &self.label, attr);
+        con.put_str(x + 2 + cells(&self.label), y, " >", attr);
+    }
+
+    fn handle(&mut self, event: &Event) -> Response {
+        match event {
+            Event::Key(Key::Enter | Key::Char(' ')) | Event::MouseUp { .. } => Response::Activated,
+            Event::MouseDown { .. } => Response::Handled,
+            _ => Response::Ignored,
+        }
+    }
+}
+
+// Drawn as [X] label, toggled by Space or a click
+pub struct CheckBox {
+    pub x: usize,
+    pub y: usize,
+    pub label: String,
+    pub checked: bool,
+}
+
+impl CheckBox {
+    pub fn new(x: usize, y: usize, label: &str, checked: bool) -> Self {
+        Self { x, y, label: label.into(), checked }
+    }
+}
+
+impl Widget for CheckBox {
+    fn rect(&self) -> Rect {
+        Rect { x: self.x, y: self.y, width: cells(&self.label) + 4, height: 1 }
+    }
+
+    fn draw(&self, con: &mut Console, x: usize, y: usize, theme: &Theme, focused: bool) {
+        let attr = if focused { theme.focused } else { theme.window };
+        let (x, y) = (x + self.x, y + self.y);
+        con.put_str(x, y, if self.checked { "[X] " } else { "[ ] " }, attr);
+        con.put_str(x + 4, y, &self.label, attr);
+    }
+
+    fn handle(&mut self, event: &Event) -> Response {
+        match event {
+            Event::Key(Key::Char(' ')) | Event::MouseDown { .. } => {
+                self.checked = !self.checked;
+                Response::Handled
+            }
+            _ => Response::Ignored,
+        }
+    }
+
+    fn cursor(&self) -> Option<(usize, usize)> {
+        Some((self.x + 1, self.y))
+    }
+}
+
+// Single line text entry, scrolls sideways when the text is wider than the field
+pub struct InputField {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub max_len: usize,
+    text: Vec<char>,
+    // cursor and scroll are in characters
+    cursor: usize,
+    scroll: usize,
+    insert: bool,
+}
+
+impl InputField {
+    pub fn new(x: usize, y: usize, width: usize, max_len: usize) -> Self {
+        Self { x, y, width: width.max(1), max_len, text: Vec::new(), cursor: 0, scroll: 0, insert: true }
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.chars().take(self.max_len).collect();
+        self.cursor = self.text.len();
+        self.fix_scroll();
+    }
+
+    fn fix_scroll(&mut self) {
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + self.width {
+            self.scroll = self.cursor + 1 - self.width;
+        }
+    }
+
+    fn type_char(&mut self, c: char) -> Response {
+        if self.insert || self.cursor == self.text.len() {
+            if self.text.len() >= self.max_len { return Response::Handled }
+            self.text.insert(self.cursor, c);
+        } else {
+            self.text[self.cursor] = c;
+        }
+        self.cursor += 1;
+        Response::Handled
+    }
+}
+
+impl Widget for InputField {
+    fn rect(&self) -> Rect {
+        Rect { x: self.x, y: self.y, width: self.width, height: 1 }
+    }
+
+    fn draw(&self, con: &mut Console, x: usize, y: usize, theme: &Theme, focused: bool) {
+        let attr = if focused { theme.focused } else { theme.control };
+        let (x, y) = (x + self.x, y + self.y);
+        con.fill(Rect { x, y, width: self.width, height: 1 }, b' ', attr);
+        let visible: String = self.text.iter().skip(self.scroll).take(self.width).collect();
+        con.put_str(x, y, &visible, attr);
+    }
+
+    fn handle(&mut self, event: &Event) -> Response {
+        let response = match *event {
+            Event::Key(Key::Char(c)) => self.type_char(c),
+            Event::Key(Key::Left) if self.cursor > 0 => { self.cursor -= 1; Response::Handled }
+            Event::Key(Key::Right) if self.cursor < self.text.len() => { self.cursor += 1; Response::Handled }
+            Event::Key(Key::Home) => { self.cursor = 0; Response::Handled }
+            Event::Key(Key::End) => { self.cursor = self.text.len(); Response::Handled }
+            Event::Key(Key::Insert) => { self.insert = !self.insert; Response::Handled }
+            Event::Key(Key::Backspace) if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.cursor);
+                Response::Handled
+            }
+            Event::Key(Key::Delete) if self.cursor < self.text.len() => {
+                self.text.remove(self.cursor);
+                Response::Handled
+            }
+            Event::Key(Key::Enter) => Response::Activated,
+            Event::MouseDown { col, .. } => {
+                self.cursor = (self.scroll + col - self.x).min(self.text.len());
+                Response::Handled
+            }
+            _ => Response::Ignored,
+        };
+        self.fix_scroll();
+        response
+    }
+
+    fn cursor(&self) -> Option<(usize, usize)> {
+        Some((self.x + self.cursor - self.scroll, self.y))
+    }
+}
+
+// Scrolling list of items, Enter or clicking the selected item activates it
+pub struct ListBox {
+    pub rect: Rect,
+    pub items: Vec<String>,
+    pub selected: usize,
+    top: usize,
+}
+
+impl ListBox {
+    pub fn new(rect: Rect, items: Vec<String>) -> Self {
+        Self { rect, items, selected: 0, top: 0 }
+    }
+
+    pub fn selected_item(&self) -> Option<&str> {
+        self.items.get(self.selected).map(|s| s.as_str())
+    }
+
+    fn select(&mut self, index: usize) {
+        if self.items.is_empty() { return }
+        self.selected = index.min(self.items.len() - 1);
+        let height = self.rect.height.max(1);
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + height {
+            self.top = self.selected + 1 - height;
+        }
+    }
+}
+
+impl Widget for ListBox {
+    fn rect(&self) -> Rect { self.rect }
+
+    fn draw(&self, con: &mut Console, x: usize, y: usize, theme: &Theme, focused: bool) {
+        let Rect { width, height, .. } = self.rect;
+        let (x, y) = (x + self.rect.x, y + self.rect.y);
+        con.fill(Rect { x, y, width, height }, b' ', theme.control);
+        for (row, (i, item)) in self.items.iter().enumerate().skip(self.top).take(height).enumerate() {
+            let attr = match (i == self.selected, focused) {
+                (true, true) => theme.focused,
+                (true, false) => theme.selected,
+                _ => theme.control,
+            };
+            if i == self.selected {
+                con.fill(Rect { x, y: y + row, width, height: 1 }, b' ', attr);
+            }
+            let text: String = item.chars().take(width.saturating_sub(2)).collect();
+            con.put_str(x + 1, y + row, &text, attr);
+        }
+    }
+
+    fn handle(&mut self, event: &Event) -> Response {
+        let page = self.rect.height.max(1);
+        match *event {
+            Event::Key(Key::Up) if self.selected > 0 => self.select(self.selected - 1),
+            Event::Key(Key::Down) if self.selected + 1 < self.items.len() => self.select(self.selected + 1),
+            Event::Key(Key::PageUp) => self.select(self.selected.saturating_sub(page)),
+            Event::Key(Key::PageDown) => self.select(self.selected + page),
+            Event::Key(Key::Home) => self.select(0),
+            Event::Key(Key::End) => self.select(self.items.len().saturating_sub(1)),
+            Event::Key(Key::Enter) if !self.items.is_empty() => return Response::Activated,
+            Event::Key(Key::Char(c)) => {
+                // jump to the next item starting with that letter
+                let c = c.to_ascii_lowercase();
+                let count = self.items.len();
+                let next = (1..=count)
+                    .map(|i| (self.selected + i) % count)
+                    .find(|&i| self.items[i].chars().next().map(|f| f.to_ascii_lowercase()) == Some(c));
+                match next {
+                    Some(i) => self.select(i),
+                    None => return Response::Ignored,
+                }
+            }
+            Event::MouseDown { row, .. } => {
+                let index = self.top + row - self.rect.y;
+                if index == self.selected && index < self.items.len() {
+                    return Response::Activated;
+                }
+                self.select(index);
+            }
+            _ => return Response::Ignored,
+        }
+        Response::Handled
+    }
+}
@@ -1,5 +1,7 @@
 use core::arch::asm;
 
+use crate::cursor::SoftwareCursor;
+
 #[derive(Copy,Clone,Default,PartialEq)]
 pub struct Vga18 {
     pub red: u8,
@@ -12,7 +14,7 @@ fn mode13h_vga_arr() -> &'static mut [[u8; 320]; 200] {
     unsafe { &mut *(0xa0000 as *mut [[u8; 320]; 200]) }
 }
 
-unsafe fn outb(port: u16, data: u8) {
+pub(crate) unsafe fn outb(port: u16, data: u8) {
     asm! {
         "out dx, al",
         in("dx") port,
@@ -20,6 +22,26 @@ unsafe fn outb(port: u16, data: u8) {
     }
 }
 
+pub(crate) unsafe fn inb(port: u16) -> u8 {
+    let data: u8;
+    asm! {
+        "in al, dx",
+        in("dx") port,
+        out("al") data,
+    }
+    data
+}
+
+// waits for the start of the next vertical retrace,
+// so palette and screen updates don't tear
+pub fn wait_vertical_retrace() {
+    unsafe {
+        // if we're already in one, wait for it to end first
+        while inb(0x3da) & 0x08 != 0 {}
+        while inb(0x3da) & 0x08 == 0 {}
+    }
+}
+
 pub fn set_vga_dac_colors(start_index: u8, colors: &[Vga18]) {
     if colors.is_empty() { return }
     unsafe { outb(0x3c8, start_index); }
@@ -35,27 +57,127 @@ pub fn set_vga_dac_colors(start_index: u8, colors: &[Vga18]) {
     }
 }
 
+pub fn get_vga_dac_colors(start_index: u8, colors: &mut [Vga18]) {
+    if colors.is_empty() { return }
+    unsafe { outb(0x3c7, start_index); }
+    for (i, color) in colors.iter_mut().enumerate() {
+        if i + start_index as usize >= 256 {
+            break
+        }
+        unsafe {
+            color.red = inb(0x3c9);
+            color.green = inb(0x3c9);
+            color.blue = inb(0x3c9);
+        }
+    }
+}
+
+// Anything 8 bit we can draw into
+// Writes outside of it are ignored, and reads give 0
+pub trait Framebuffer {
+    fn get_pixel(&self, x: isize, y: isize) -> u8;
+    fn put_pixel(&mut self, x: isize, y: isize, color: u8);
+}
+
+// The mode 13h screen itself, for drawing straight into VRAM
+pub struct Mode13hScreen;
+
+impl Framebuffer for Mode13hScreen {
+    fn get_pixel(&self, x: isize, y: isize) -> u8 {
+        if (0..320).contains(&x) && (0..200).contains(&y) {
+            mode13h_vga_arr()[y as usize][x as usize]
+        } else { 0 }
+    }
+
+    fn put_pixel(&mut self, x: isize, y: isize, color: u8) {
+        if (0..320).contains(&x) && (0..200).contains(&y) {
+            mode13h_vga_arr()[y as usize][x as usize] = color;
+        }
+    }
+}
+
 pub struct Mode13hDisplay {
-    buffer: [[u8; 320]; 200]
+    buffer: [[u8; 320]; 200],
+    // drawn over the buffer on the screen only, so moving it doesn't need a redraw
+    cursor: Option<SoftwareCursor>,
 }
 
 impl Default for Mode13hDisplay {
     fn default() -> Self {
-        Self { buffer: [[0; 320]; 200] }
+        Self { buffer: [[0; 320]; 200], cursor: None }
+    }
+}
+
+impl Framebuffer for Mode13hDisplay {
+    fn get_pixel(&self, x: isize, y: isize) -> u8 {
+        if (0..320).contains(&x) && (0..200).contains(&y) {
+            self.buffer[y as usize][x as usize]
+        } else { 0 }
+    }
+
+    fn put_pixel(&mut self, x: isize, y: isize, color: u8) {
+        if (0..320).contains(&x) && (0..200).contains(&y) {
+            self.buffer[y as usize][x as usize] = color;
+        }
     }
 }
 
 impl Mode13hDisplay {
-    #[allow(unused)]
-    pub fn flush(&self) {
+    // Copies the buffer to the screen, with the cursor on top
+    // The cursor goes into the buffer for the copy, so the screen never goes a frame without it
+    pub fn flush(&mut self) {
         let vga = mode13h_vga_arr();
+        let mut cursor = match self.cursor.take() {
+            Some(cursor) => cursor,
+            None => {
+                *vga = self.buffer;
+                return;
+            }
+        };
+        // the old background is stale, save what's under it in the new frame instead
+        cursor.forget();
+        cursor.show(self);
         *vga = self.buffer;
+        cursor.restore_background(self);
+        self.cursor = Some(cursor);
+    }
+
+    pub fn set_cursor(&mut self, cursor: Option<SoftwareCursor>) {
+        if let Some(old) = &mut self.cursor {
+            old.hide(&mut Mode13hScreen);
+        }
+        self.cursor = cursor;
+        if let Some(cursor) = &mut self.cursor {
+            cursor.show(&mut Mode13hScreen);
+        }
+    }
+
+    // Moves the cursor on the screen, without flushing the buffer
+    pub fn move_cursor(&mut self, x: isize, y: isize) {
+        if let Some(cursor) = &mut self.cursor {
+            cursor.move_to(&mut Mode13hScreen, x, y);
+        }
+    }
+
+    // The back buffer, a row at a time from the top
+    pub fn pixels(&self) -> &[u8] {
+        self.buffer.as_flattened()
     }
 
     pub fn clear(&mut self) {
         self.buffer = [[0; 320]; 200];
     }
 
+    pub fn fill_rect(&mut self, x: isize, y: isize, width: usize, height: usize, color: u8) {
+        let x_start = x.clamp(0, 320) as usize;
+        let x_end = (x + width as isize).clamp(0, 320) as usize;
+        let y_start = y.clamp(0, 200) as usize;
+        let y_end = (y + height as isize).clamp(0, 200) as usize;
+        for line in &mut self.buffer[y_start..y_end] {
+            line[x_start..x_end].fill(color);
+        }
+    }
+
     pub fn copy_to_screen(&mut self, screen_col: isize, screen_line: isize, src_width: usize, src_height: usize, bytes: &[u8]) {
         let x = screen_col;
         let y = screen_line;
@@ -84,4 +206,25 @@ impl Mode13hDisplay {
             self.buffer[l_y as usize][x_adj as usize..x_adj as usize+line_len as usize].copy_from_slice(&bytes[src_off..src_off+line_len as usize]);
         }
     }
+
+    // same as copy_to_screen, but every source pixel becomes a scale x scale block
+    pub fn copy_to_screen_scaled(&mut self, screen_col: isize, screen_line: isize, src_width: usize, src_height: usize, scale: usize, bytes: &[u8]) {
+        if scale <= 1 {
+            return self.copy_to_screen(screen_col, screen_line, src_width, src_height, bytes);
+        }
+
+        let x_start = screen_col.clamp(0, 320);
+        let x_end = (screen_col + (src_width * scale) as isize).clamp(0, 320);
+        let y_start = screen_line.clamp(0, 200);
+        let y_end = (screen_line + (src_height * scale) as isize).clamp(0, 200);
+
+        for l_y in y_start..y_end {
+            let src_line = (l_y - screen_line) as usize / scale;
+            let src_row = &bytes[src_line * src_width..(src_line + 1) * src_width];
+            let line = &mut self.buffer[l_y as usize];
+            for l_x in x_start..x_end {
+                line[l_x as usize] = src_row[(l_x - screen_col) as usize / scale];
+            }
+        }
+    }
 }